@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "./MockERC20.sol";
+
+// The classic honeypot: everyone buying from the pair is blacklisted and can never send again
+contract BlacklistERC20 is MockERC20 {
+    mapping(address => bool) public blacklisted;
+
+    constructor(string memory _name, string memory _symbol, uint8 _decimals)
+        MockERC20(_name, _symbol, _decimals)
+    {}
+
+    function _update(address from, address to, uint256 amount) internal override {
+        require(!blacklisted[from], "BlacklistERC20: blacklisted");
+        if (from == pair && to != owner) {
+            blacklisted[to] = true;
+        }
+        _move(from, to, amount);
+    }
+}
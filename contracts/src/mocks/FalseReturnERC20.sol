@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "./MockERC20.sol";
+
+// Reports failed transfers by returning false instead of reverting
+contract FalseReturnERC20 is MockERC20 {
+    constructor(string memory _name, string memory _symbol, uint8 _decimals)
+        MockERC20(_name, _symbol, _decimals)
+    {}
+
+    function transfer(address to, uint256 amount) external override returns (bool) {
+        if (balanceOf[msg.sender] < amount) {
+            return false;
+        }
+        _update(msg.sender, to, amount);
+        return true;
+    }
+
+    function transferFrom(address from, address to, uint256 amount) external override returns (bool) {
+        uint256 allowed = allowance[from][msg.sender];
+        if (balanceOf[from] < amount || allowed < amount) {
+            return false;
+        }
+        if (allowed != type(uint256).max) {
+            allowance[from][msg.sender] = allowed - amount;
+        }
+        _update(from, to, amount);
+        return true;
+    }
+}
@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "./MockERC20.sol";
+
+// Rejects every transfer above maxTxAmount, except the ones made by the owner
+contract MaxTxERC20 is MockERC20 {
+    uint256 public maxTxAmount;
+
+    constructor(string memory _name, string memory _symbol, uint8 _decimals, uint256 _maxTxAmount)
+        MockERC20(_name, _symbol, _decimals)
+    {
+        maxTxAmount = _maxTxAmount;
+    }
+
+    function _update(address from, address to, uint256 amount) internal override {
+        require(from == owner || to == owner || amount <= maxTxAmount, "MaxTxERC20: max tx amount");
+        _move(from, to, amount);
+    }
+}
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "./MockERC20.sol";
+
+// Takes taxBps of every buy and sell and keeps it in the contract, wallet transfers are free
+contract TaxedERC20 is MockERC20 {
+    uint256 public taxBps;
+
+    constructor(string memory _name, string memory _symbol, uint8 _decimals, uint256 _taxBps)
+        MockERC20(_name, _symbol, _decimals)
+    {
+        taxBps = _taxBps;
+    }
+
+    function _update(address from, address to, uint256 amount) internal override {
+        if (pair != address(0) && (from == pair || to == pair)) {
+            uint256 tax = amount * taxBps / 10000;
+            _move(from, address(this), tax);
+            amount -= tax;
+        }
+        _move(from, to, amount);
+    }
+}
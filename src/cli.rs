@@ -0,0 +1,527 @@
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
+use ethers::providers::{Middleware, PubsubClient};
+use ethers::types::{Transaction, TxHash, H160, U64};
+use log::{info, warn};
+use serde::Serialize;
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use tokio::sync::broadcast;
+
+use crate::config::FactoryRegistry;
+use crate::constants::{Env, EnvOverrides, DEFAULT_SENDER};
+use crate::honeypot::{HoneypotConfig, HoneypotFilter};
+use crate::inspectors::call_tracer::format_call_tree;
+use crate::mempool::MempoolWatcher;
+use crate::paths::generate_triangular_paths;
+use crate::pools::{find_pool, load_all_pools, Pool};
+use crate::replay::replay_transaction;
+use crate::risk::RiskThresholds;
+use crate::server::serve;
+use crate::simulator::EvmSimulator;
+
+#[derive(Parser, Debug)]
+#[command(about = "EVM simulation: REVM + Foundry")]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
+    /// RPC endpoint (http(s)://, ws(s):// or IPC path), overrides RPC_URL
+    #[arg(long, global = true)]
+    pub rpc_url: Option<String>,
+    /// Expected chain id, overrides CHAIN_ID
+    #[arg(long, global = true)]
+    pub chain_id: Option<u64>,
+    /// Max requests per second sent to the RPC, overrides RPC_RATE_LIMIT
+    #[arg(long, global = true)]
+    pub rate_limit: Option<u32>,
+    /// Factory registry file, defaults to ./config/<chain>.toml
+    #[arg(long, global = true, env = "FACTORY_CONFIG")]
+    pub factory_config: Option<PathBuf>,
+    /// Print the result as JSON instead of human-readable text
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(flatten)]
+    pub honeypot: HoneypotArgs,
+}
+
+impl GlobalArgs {
+    pub fn env_overrides(&self) -> EnvOverrides {
+        EnvOverrides {
+            rpc_url: self.rpc_url.clone(),
+            chain_id: self.chain_id,
+            rate_limit: self.rate_limit,
+        }
+    }
+}
+
+// Overrides of the [honeypot] section of the factory registry
+#[derive(Args, Debug)]
+pub struct HoneypotArgs {
+    /// Risk limits to start from: default, conservative or aggressive
+    #[arg(long, global = true)]
+    pub strategy: Option<String>,
+    /// WETH swapped in the buy/sell tests, in whole units
+    #[arg(long, global = true)]
+    pub weth_swap_amount: Option<f64>,
+    /// Stablecoins swapped in the buy/sell tests, in whole units
+    #[arg(long, global = true)]
+    pub stable_swap_amount: Option<u32>,
+    /// Tokens sent in the transfer tests, in whole units
+    #[arg(long, global = true)]
+    pub transfer_test_amount: Option<u32>,
+    /// Highest buy, sell and transfer tax accepted, as a fraction (0.1 = 10%)
+    #[arg(long, global = true)]
+    pub max_tax: Option<f64>,
+    /// Least WETH a pool needs to be tested, in whole units
+    #[arg(long, global = true)]
+    pub min_liquidity: Option<f64>,
+    /// Test proxy tokens instead of rejecting them outright
+    #[arg(long, global = true)]
+    pub allow_proxies: bool,
+}
+
+impl HoneypotArgs {
+    pub fn apply(&self, config: &mut HoneypotConfig) -> Result<()> {
+        if let Some(strategy) = &self.strategy {
+            config.thresholds = RiskThresholds::for_strategy(strategy)
+                .ok_or(anyhow!("Unknown strategy {strategy:?}"))?;
+        }
+        if let Some(amount) = self.weth_swap_amount {
+            config.weth_swap_amount = amount;
+        }
+        if let Some(amount) = self.stable_swap_amount {
+            config.stable_swap_amount = amount;
+        }
+        if let Some(amount) = self.transfer_test_amount {
+            config.transfer_test_amount = amount;
+        }
+        if let Some(max_tax) = self.max_tax {
+            config.thresholds.set_max_tax(max_tax);
+        }
+        if let Some(min_liquidity) = self.min_liquidity {
+            config.thresholds.min_liquidity = min_liquidity;
+        }
+        if self.allow_proxies {
+            config.reject_proxies = false;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Sync pools from the factories of the registry and update the pool cache
+    SyncPools,
+    /// Run the honeypot checks against a single token
+    CheckToken {
+        token: H160,
+        /// WETH pool used for the buy/sell simulation, defaults to the first pool paired with WETH
+        #[arg(long)]
+        pool: Option<H160>,
+        /// Also buy and sell with native ETH through the UniswapV2 router of the pool's factory
+        #[arg(long)]
+        router: bool,
+        /// Dump the fork state fetched during the check to a JSON fixture for offline replay
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Run the honeypot filter over the synced pools
+    Filter {
+        /// Only test the first N pools
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Generate triangular arbitrage paths starting from a token
+    Paths {
+        #[arg(long)]
+        start: H160,
+    },
+    /// Re-execute a transaction on a fork of the block before it
+    SimulateTx { hash: TxHash },
+    /// Print the call tree of a transaction executed on a fork of the block before it
+    TraceTx { hash: TxHash },
+    /// Replay a mined transaction after the ones before it in its block and diff against the receipt
+    ReplayTx { hash: TxHash },
+    /// Serve honeypot checks and simulations over JSON-RPC
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:9545")]
+        addr: SocketAddr,
+    },
+    /// Simulate pending transactions against the head and print their effects (needs ws/ipc)
+    WatchMempool {
+        /// Subscribe to full pending transactions instead of fetching each hash
+        #[arg(long)]
+        full_txs: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncPoolsReport {
+    pub chain_id: u64,
+    pub pools: usize,
+    pub pool_cache: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FilterReport {
+    pub tested_pools: usize,
+    pub verified_pools: usize,
+    pub safe_tokens: Vec<H160>,
+    pub honeypots: Vec<H160>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PathReport {
+    pub pools: Vec<H160>,
+    pub zero_for_one: Vec<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulateTxReport {
+    pub hash: TxHash,
+    pub fork_block: U64,
+    pub success: bool,
+    pub gas_used: Option<u64>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+fn print_report<T: Serialize>(json: bool, report: &T, human: impl FnOnce(&T)) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+    } else {
+        human(report);
+    }
+    Ok(())
+}
+
+pub async fn run<M>(cli: &Cli, env: &Env, provider: Arc<M>) -> Result<()>
+where
+    M: Middleware + 'static,
+    M::Provider: PubsubClient,
+{
+    let json = cli.global.json;
+    let registry = match &cli.global.factory_config {
+        Some(path) => FactoryRegistry::load(path)?,
+        None => FactoryRegistry::for_chain(env.chain_id)?,
+    };
+    let mut config = registry.honeypot.clone();
+    config.routers = registry.routers();
+    cli.global.honeypot.apply(&mut config)?;
+
+    match &cli.command {
+        Command::SyncPools => {
+            let pools = load_all_pools(provider.clone(), &registry).await?;
+            let report = SyncPoolsReport {
+                chain_id: registry.chain_id,
+                pools: pools.len(),
+                pool_cache: registry.pool_cache.clone(),
+            };
+            print_report(json, &report, |r| {
+                println!("Synced {} pools into {}", r.pools, r.pool_cache.display())
+            })
+        }
+        Command::CheckToken { token, pool, router, record } => {
+            config.check_router |= *router;
+            let mut filter = new_honeypot_filter(env, provider.clone(), config).await?;
+            let pool = match pool {
+                Some(pool) => {
+                    // The buy and sell are simulated with WETH, other pairs cannot be seeded
+                    let weth = filter.safe_tokens.weth;
+                    let pair = filter
+                        .simulator
+                        .v2_pool_tokens(*pool)
+                        .map_err(|e| anyhow!("{pool:?} is not a UniswapV2 pool: {e:?}"))?;
+                    if pair != (*token, weth) && pair != (weth, *token) {
+                        return Err(anyhow!(
+                            "{pool:?} does not pair {token:?} with WETH ({weth:?}), pass a WETH pool or leave --pool out"
+                        ));
+                    }
+                    *pool
+                }
+                None => {
+                    let pools = load_all_pools(provider.clone(), &registry).await?;
+                    find_pool(&pools, *token, filter.safe_tokens.weth)
+                        .map(|pool| pool.address)
+                        .ok_or(anyhow!("No pool paired with WETH found for {token:?}"))?
+                }
+            };
+
+            let report = filter.check_token(*token, pool).await?;
+            if let Some(path) = record {
+                filter.simulator.record_state()?.save(path)?;
+                info!("Recorded the fork state to {}", path.display());
+            }
+            print_report(json, &report, |r| {
+                println!("Token:        {:?}", r.token);
+                println!("Pool:         {:?}", r.pool);
+                println!("Proxy:        {}", r.is_proxy);
+                match r.owner {
+                    Some(owner) => println!("Owner:        {owner:?}"),
+                    None => println!("Owner:        -"),
+                }
+                println!("Swappable:    {}", r.swappable);
+                println!("Buy tax:      {:.2}%", r.buy_tax * 100.0);
+                println!("Sell tax:     {:.2}%", r.sell_tax * 100.0);
+                println!("Transferable: {}", r.transferable);
+                println!("Transfer tax: {:.2}%", r.transfer_tax * 100.0);
+                if let Some(analysis) = &r.transfer_analysis {
+                    println!(
+                        "Tax detail:   {} bps via {:?} (burned {}, contract {}, redirected to {} wallets, reflected {})",
+                        analysis.tax_bps,
+                        analysis.mechanisms,
+                        analysis.burned,
+                        analysis.to_contract,
+                        analysis.redirected.len(),
+                        analysis.reflected
+                    );
+                }
+                if let Some(router) = &r.router_swap {
+                    println!("Router:       {:?}", router.router);
+                    println!("  Swappable:  {}", router.swappable);
+                    println!("  Buy tax:    {:.2}%", router.buy_tax * 100.0);
+                    println!("  Sell tax:   {:.2}%", router.sell_tax * 100.0);
+                }
+                if let Some(compliance) = &r.compliance {
+                    println!(
+                        "ERC-20:       {}",
+                        if compliance.passed() { "compliant" } else { "not compliant" }
+                    );
+                    for verdict in &compliance.checks {
+                        match &verdict.detail {
+                            Some(detail) => {
+                                println!("  {:?}: {} ({detail})", verdict.check, verdict.passed)
+                            }
+                            None => println!("  {:?}: {}", verdict.check, verdict.passed),
+                        }
+                    }
+                }
+                if !r.anomalies.is_empty() {
+                    println!("Anomalies:    {:?}", r.anomalies);
+                }
+                if let Some(risk) = &r.risk {
+                    println!("Risk score:   {}/100 (honeypot: {})", risk.score, risk.is_honeypot);
+                    for reason in &risk.reasons {
+                        let limit = if reason.limit { " (over the limit)" } else { "" };
+                        println!("  +{:<3} {}{limit}", reason.points, reason.reason);
+                    }
+                }
+            })
+        }
+        Command::Filter { limit } => {
+            let pools = load_all_pools(provider.clone(), &registry).await?;
+            let pools: Vec<Pool> = match limit {
+                Some(limit) => pools.into_iter().take(*limit).collect(),
+                None => pools,
+            };
+
+            let mut filter = new_honeypot_filter(env, provider.clone(), config).await?;
+            filter.filter_tokens(&pools).await?;
+
+            let verified_pools = pools
+                .iter()
+                .filter(|pool| {
+                    let token0_verified = filter.safe_token_info.contains_key(&pool.token0)
+                        || filter.token_info.contains_key(&pool.token0);
+                    let token1_verified = filter.safe_token_info.contains_key(&pool.token1)
+                        || filter.token_info.contains_key(&pool.token1);
+                    token0_verified && token1_verified
+                })
+                .count();
+
+            let report = FilterReport {
+                tested_pools: pools.len(),
+                verified_pools,
+                safe_tokens: filter.token_info.keys().copied().collect(),
+                honeypots: filter.honeypot.keys().copied().collect(),
+            };
+            print_report(json, &report, |r| {
+                println!("Tested pools:   {}", r.tested_pools);
+                println!("Verified pools: {}", r.verified_pools);
+                println!("Safe tokens:    {}", r.safe_tokens.len());
+                println!("Honeypots:      {}", r.honeypots.len());
+                for token in &r.honeypots {
+                    println!("  {token:?}");
+                }
+            })
+        }
+        Command::Paths { start } => {
+            let pools = load_all_pools(provider.clone(), &registry).await?;
+            let paths = generate_triangular_paths(&pools, *start);
+
+            let report: Vec<PathReport> = paths
+                .iter()
+                .map(|path| PathReport {
+                    pools: (0..path.nhop).map(|i| path.get_pool(i).address).collect(),
+                    zero_for_one: (0..path.nhop).map(|i| path.get_zero_for_one(i)).collect(),
+                })
+                .collect();
+            print_report(json, &report, |r| {
+                for path in r {
+                    let hops: Vec<String> =
+                        path.pools.iter().map(|pool| format!("{pool:?}")).collect();
+                    println!("{}", hops.join(" -> "));
+                }
+                println!("{} paths", r.len());
+            })
+        }
+        Command::SimulateTx { hash } => {
+            let tx = provider
+                .get_transaction(*hash)
+                .await?
+                .ok_or(anyhow!("Transaction {hash:?} not found"))?;
+
+            let mut simulator = fork_for_tx(provider.clone(), &tx).await?;
+            let fork_block = simulator.block_number;
+            let report = match simulator.run_pending_tx(&tx) {
+                Ok(result) => SimulateTxReport {
+                    hash: *hash,
+                    fork_block,
+                    success: true,
+                    gas_used: Some(result.gas_used),
+                    output: Some(format!("0x{}", hex::encode(&result.output))),
+                    error: None,
+                },
+                Err(e) => SimulateTxReport {
+                    hash: *hash,
+                    fork_block,
+                    success: false,
+                    gas_used: None,
+                    output: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            print_report(json, &report, |r| {
+                println!("Transaction: {:?}", r.hash);
+                println!("Fork block:  {}", r.fork_block);
+                println!("Success:     {}", r.success);
+                if let Some(gas_used) = r.gas_used {
+                    println!("Gas used:    {gas_used}");
+                }
+                if let Some(output) = &r.output {
+                    println!("Output:      {output}");
+                }
+                if let Some(error) = &r.error {
+                    println!("Error:       {error}");
+                }
+            })
+        }
+        Command::TraceTx { hash } => {
+            let tx = provider
+                .get_transaction(*hash)
+                .await?
+                .ok_or(anyhow!("Transaction {hash:?} not found"))?;
+            let mut simulator = fork_for_tx(provider.clone(), &tx).await?;
+            let trace = simulator.trace_tx(&tx)?;
+            print_report(json, &trace, |trace| print!("{}", format_call_tree(trace)))
+        }
+        Command::ReplayTx { hash } => {
+            let report = replay_transaction(provider.clone(), *hash).await?;
+            print_report(json, &report, |r| {
+                println!("Transaction: {:?}", r.hash);
+                println!("Block:       {} (index {})", r.block_number, r.transaction_index);
+                println!("Replayed:    {} transactions before it", r.replayed_before);
+                for (hash, error) in &r.diverged {
+                    println!("  failed {hash:?}: {error}");
+                }
+                println!("Status:      {} (receipt: {})", r.actual.success, r.expected.success);
+                println!("Gas used:    {} (receipt: {})", r.actual.gas_used, r.expected.gas_used);
+                println!(
+                    "Logs:        {} (receipt: {})",
+                    r.actual.logs.len(),
+                    r.expected.logs.len()
+                );
+                if r.matches() {
+                    println!("Simulation matches the receipt");
+                } else {
+                    println!("Mismatches:");
+                    for mismatch in &r.mismatches {
+                        println!("  - {mismatch}");
+                    }
+                }
+            })
+        }
+        Command::Serve { addr } => {
+            let chain = env
+                .chain_config()
+                .ok_or(anyhow!("Unsupported chain id {}", env.chain_id.as_u64()))?;
+            let pools = load_all_pools(provider.clone(), &registry).await?;
+            serve(provider, chain, config, pools, *addr).await
+        }
+        Command::WatchMempool { full_txs } => {
+            let (sender, mut receiver) = broadcast::channel(512);
+            let mut watcher = MempoolWatcher::new(provider.clone(), *DEFAULT_SENDER, *full_txs);
+            let watch = tokio::spawn(async move { watcher.run(sender).await });
+
+            loop {
+                match receiver.recv().await {
+                    Ok(effects) => print_report(json, &effects, |e| {
+                        println!(
+                            "{:?} @ {} success={} gas={} pools={} transfers={}",
+                            e.tx.hash,
+                            e.block_number,
+                            e.success,
+                            e.gas_used,
+                            e.touched_pools.len(),
+                            e.transfers.len()
+                        );
+                        for reserve in &e.reserves {
+                            println!(
+                                "  {:?}: {} / {}",
+                                reserve.pool, reserve.reserve0, reserve.reserve1
+                            );
+                        }
+                    })?,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Skipped {skipped} pending transactions");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+
+            watch.await?
+        }
+    }
+}
+
+// Mined transactions are replayed on top of the previous block in the env of their own block,
+// pending ones on the head
+async fn fork_for_tx<M: Middleware + 'static>(
+    provider: Arc<M>,
+    tx: &Transaction,
+) -> Result<EvmSimulator<M>> {
+    let fork_block = match tx.block_number {
+        Some(block_number) => block_number
+            .checked_sub(U64::one())
+            .ok_or(anyhow!("{:?} is in the genesis block, there is no block to fork", tx.hash))?,
+        None => provider.get_block_number().await?,
+    };
+
+    let mut simulator = EvmSimulator::new(provider.clone(), *DEFAULT_SENDER, fork_block);
+    simulator.load_block_env().await?;
+    if let Some(block_number) = tx.block_number {
+        let block = provider
+            .get_block(block_number)
+            .await?
+            .ok_or(anyhow!("Block {block_number} not found"))?;
+        simulator.apply_header(&block);
+    }
+    simulator.apply_spec();
+    Ok(simulator)
+}
+
+async fn new_honeypot_filter<M: Middleware + 'static>(
+    env: &Env,
+    provider: Arc<M>,
+    config: HoneypotConfig,
+) -> Result<HoneypotFilter<M>> {
+    let chain =
+        env.chain_config().ok_or(anyhow!("Unsupported chain id {}", env.chain_id.as_u64()))?;
+    HoneypotFilter::from_latest_block(provider, chain, config).await
+}
@@ -0,0 +1,58 @@
+use ethers::types::{H160, U256};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceCheck {
+    // transfer(recipient, amount) succeeds and credits the recipient
+    Transfer,
+    // approve(spender, amount) then transferFrom(owner, recipient, amount) from the spender,
+    // which is how routers pull the tokens of a sell
+    ApproveTransferFrom,
+    // transferFrom lowers the allowance by the amount moved
+    AllowanceDecrement,
+    // transfers of 0 must be treated as normal transfers
+    ZeroAmountTransfer,
+    // sending to yourself succeeds and does not mint anything
+    SelfTransfer,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckVerdict {
+    pub check: ComplianceCheck,
+    pub passed: bool,
+    // why the check failed, or what was odd about it
+    pub detail: Option<String>,
+}
+
+impl CheckVerdict {
+    pub fn pass(check: ComplianceCheck) -> Self {
+        Self { check, passed: true, detail: None }
+    }
+
+    pub fn fail(check: ComplianceCheck, detail: impl Into<String>) -> Self {
+        Self { check, passed: false, detail: Some(detail.into()) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComplianceReport {
+    pub token: H160,
+    // balance dealt to the owner, every check moves a fraction of it
+    pub amount: U256,
+    pub checks: Vec<CheckVerdict>,
+}
+
+impl ComplianceReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|verdict| verdict.passed)
+    }
+
+    pub fn failed(&self) -> Vec<ComplianceCheck> {
+        self.checks.iter().filter(|verdict| !verdict.passed).map(|verdict| verdict.check).collect()
+    }
+
+    pub fn verdict(&self, check: ComplianceCheck) -> Option<&CheckVerdict> {
+        self.checks.iter().find(|verdict| verdict.check == check)
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result};
+use cfmms::dex::DexVariant as CfmmsDexVariant;
+use ethers::types::{H160, U64};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::honeypot::HoneypotConfig;
+use crate::pools::DexVariant;
+
+pub static CONFIG_DIR: &str = "config";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Invalid environment:{}", describe_env_issues(.missing, .invalid))]
+    Env { missing: Vec<String>, invalid: Vec<(String, String)> },
+    #[error("RPC reports chain id {actual}, but CHAIN_ID is set to {expected}")]
+    ChainIdMismatch { expected: U64, actual: U64 },
+    #[error("RPC request failed: {0}")]
+    Rpc(String),
+}
+
+fn describe_env_issues(missing: &[String], invalid: &[(String, String)]) -> String {
+    let mut out = String::new();
+    for key in missing {
+        out.push_str(&format!("\n  - {key} is not set"));
+    }
+    for (key, reason) in invalid {
+        out.push_str(&format!("\n  - {key} is invalid: {reason}"));
+    }
+    out
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FactoryConfig {
+    pub name: String,
+    pub address: H160,
+    pub variant: DexVariant,
+    pub fee: u64,
+    pub deployed_block: u64,
+    // UniswapV2Router02 (or a fork of it) trading on this factory's pairs
+    pub router: Option<H160>,
+}
+
+impl FactoryConfig {
+    pub fn cfmms_variant(&self) -> CfmmsDexVariant {
+        match self.variant {
+            DexVariant::UniswapV2 => CfmmsDexVariant::UniswapV2,
+            DexVariant::UniswapV3 => CfmmsDexVariant::UniswapV3,
+        }
+    }
+}
+
+// A per-chain list of the factories to scan for pools
+// The files live under ./config and are named after the chain (mainnet.toml, arbitrum.toml, ...)
+#[derive(Debug, Clone, Deserialize)]
+pub struct FactoryRegistry {
+    pub chain_id: u64,
+    pub pool_cache: PathBuf,
+    pub factories: Vec<FactoryConfig>,
+    // Optional [honeypot] section, every field left out keeps its default
+    #[serde(default)]
+    pub honeypot: HoneypotConfig,
+}
+
+impl FactoryRegistry {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read factory config {}: {e:?}", path.display()))?;
+
+        let registry: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&raw)?,
+            _ => toml::from_str(&raw)?,
+        };
+
+        Ok(registry)
+    }
+
+    // Factory -> router, for the router buy/sell simulation
+    pub fn routers(&self) -> HashMap<H160, H160> {
+        self.factories
+            .iter()
+            .filter_map(|factory| factory.router.map(|router| (factory.address, router)))
+            .collect()
+    }
+
+    // Loads ./config/<chain name>.toml for the given chain id
+    pub fn for_chain(chain_id: U64) -> Result<Self> {
+        let name = chain_name(chain_id)
+            .ok_or(anyhow!("No factory config available for chain id {chain_id}"))?;
+        let registry = Self::load(Path::new(CONFIG_DIR).join(format!("{name}.toml")))?;
+
+        if registry.chain_id != chain_id.as_u64() {
+            return Err(anyhow!(
+                "Factory config for {name} is set to chain id {}, expected {chain_id}",
+                registry.chain_id
+            ));
+        }
+
+        Ok(registry)
+    }
+}
+
+pub fn chain_name(chain_id: U64) -> Option<&'static str> {
+    match chain_id.as_u64() {
+        1 => Some("mainnet"),
+        56 => Some("bsc"),
+        8453 => Some("base"),
+        42161 => Some("arbitrum"),
+        _ => None,
+    }
+}
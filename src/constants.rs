@@ -1,9 +1,13 @@
 use ethers::{
     prelude::Lazy,
-    types::{Address, Bytes, U256, U64},
+    providers::Middleware,
+    types::{Address, Bytes, H256, U256, U64},
+    utils::keccak256,
 };
 use ethers_core::types::H160;
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
+
+use crate::config::ConfigError;
 
 pub static WEI: Lazy<U256> = Lazy::new(|| U256::from(10).pow(U256::from(18)));
 pub static GWEI: Lazy<U256> = Lazy::new(|| U256::from(10).pow(U256::from(9)));
@@ -13,32 +17,243 @@ pub static DEFAULT_SENDER: Lazy<H160> =
 pub static DEFAULT_RECIPIENT: Lazy<H160> =
     Lazy::new(|| H160::from_str("0x4E17607Fb72C01C280d7b5c41Ba9A2109D74a32C").unwrap());
 
-pub static DEFAULT_CHAIN_ID: U64 = U64::one();
+// Gas limit of the helper calls (balanceOf, approve, swaps through the simulator contract)
+pub static DEFAULT_GAS_LIMIT: u64 = 5000000;
+// Intrinsic gas of a plain call, the lower bound of gas estimation
+pub static TX_BASE_GAS: u64 = 21000;
+
 pub static ZERO_ADDRESS: Lazy<Address> =
     Lazy::new(|| Address::from_str("0x0000000000000000000000000000000000000000").unwrap());
-pub static WETH_DECIMALS: u8 = 18;
-pub static WETH_BALANCE_SLOT: u32 = 3;
+pub static DEAD_ADDRESS: Lazy<Address> =
+    Lazy::new(|| Address::from_str("0x000000000000000000000000000000000000dEaD").unwrap());
+// Plain EOA receiving the analysed transfers, it holds nothing and has no code on any chain
+pub static TRANSFER_RECIPIENT: Lazy<H160> =
+    Lazy::new(|| H160::from_str("0x00000000000000000000000000000000000bEEF0").unwrap());
+// Plain EOA approved by the owner to move its tokens in the transferFrom checks
+pub static APPROVED_SPENDER: Lazy<H160> =
+    Lazy::new(|| H160::from_str("0x00000000000000000000000000000000000bEEF1").unwrap());
+
+// Multicall3 is deployed at the same address on every supported chain
+pub static MULTICALL3_ADDRESS: Lazy<H160> =
+    Lazy::new(|| H160::from_str("0xcA11bde05977b3631167028862bE2a173976CA11").unwrap());
 
-pub fn get_env(key: &str) -> String {
-    std::env::var(key).unwrap()
+pub static TRANSFER_TOPIC: Lazy<H256> =
+    Lazy::new(|| H256::from(keccak256("Transfer(address,address,uint256)")));
+
+// How far back to look for Transfer logs when searching token holders
+pub static HOLDER_LOOKBACK_BLOCKS: u64 = 2000;
+
+pub fn get_env(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+// RPC_URL takes precedence, WSS_URL (with an optional API_KEY) is still read for older setups
+fn get_rpc_url() -> Option<String> {
+    get_env("RPC_URL").or_else(|| {
+        let wss_url = get_env("WSS_URL")?;
+        match get_env("API_KEY") {
+            Some(api_key) => Some(format!("{wss_url}?key={api_key}")),
+            None => Some(wss_url),
+        }
+    })
+}
+
+// Values passed on the command line, they take precedence over the environment
+#[derive(Debug, Clone, Default)]
+pub struct EnvOverrides {
+    pub rpc_url: Option<String>,
+    pub chain_id: Option<u64>,
+    pub rate_limit: Option<u32>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Env {
-    // pub https_url: String,
-    pub wss_url: String,
+    // http(s)://, ws(s):// or an IPC path
+    pub rpc_url: String,
     pub chain_id: U64,
-    pub api_key: String,
+    // max requests per second sent to the RPC, unlimited if not set
+    pub rate_limit: Option<u32>,
 }
 
 impl Env {
-    pub fn new() -> Self {
-        Env {
-            // https_url: get_env("HTTPS_URL"),
-            wss_url: get_env("WSS_URL"),
-            chain_id: U64::from_str(&get_env("CHAIN_ID")).unwrap(),
-            api_key: get_env("API_KEY"),
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_env_with(&EnvOverrides::default())
+    }
+
+    // Reads every key before failing so that all missing/invalid values are reported at once
+    pub fn from_env_with(overrides: &EnvOverrides) -> Result<Self, ConfigError> {
+        let mut missing = Vec::new();
+        let mut invalid = Vec::new();
+
+        let rpc_url = overrides.rpc_url.clone().or_else(get_rpc_url);
+        if rpc_url.is_none() {
+            missing.push("RPC_URL (or WSS_URL)".to_string());
+        }
+
+        let chain_id = match (overrides.chain_id, get_env("CHAIN_ID")) {
+            (Some(chain_id), _) => Some(chain_id),
+            (None, Some(raw)) => match raw.parse::<u64>() {
+                Ok(chain_id) => Some(chain_id),
+                Err(e) => {
+                    invalid.push(("CHAIN_ID".to_string(), format!("{raw:?} ({e})")));
+                    None
+                }
+            },
+            (None, None) => {
+                missing.push("CHAIN_ID".to_string());
+                None
+            }
+        };
+
+        let rate_limit = match (overrides.rate_limit, get_env("RPC_RATE_LIMIT")) {
+            (Some(rate_limit), _) => Some(rate_limit),
+            (None, Some(raw)) => match raw.parse::<u32>() {
+                Ok(rate_limit) if rate_limit > 0 => Some(rate_limit),
+                Ok(_) => {
+                    invalid.push(("RPC_RATE_LIMIT".to_string(), "must be greater than 0".into()));
+                    None
+                }
+                Err(e) => {
+                    invalid.push(("RPC_RATE_LIMIT".to_string(), format!("{raw:?} ({e})")));
+                    None
+                }
+            },
+            (None, None) => None,
+        };
+
+        match (rpc_url, chain_id) {
+            (Some(rpc_url), Some(chain_id)) if invalid.is_empty() => {
+                Ok(Env { rpc_url, chain_id: U64::from(chain_id), rate_limit })
+            }
+            _ => Err(ConfigError::Env { missing, invalid }),
+        }
+    }
+
+    // Makes sure we are not simulating against a different network than the one configured
+    pub async fn validate_chain_id<M: Middleware>(&self, provider: &M) -> Result<(), ConfigError> {
+        let actual = provider
+            .get_chainid()
+            .await
+            .map_err(|e| ConfigError::Rpc(format!("eth_chainId failed: {e:?}")))?;
+        let actual = U64::from(actual.as_u64());
+
+        if actual != self.chain_id {
+            return Err(ConfigError::ChainIdMismatch { expected: self.chain_id, actual });
         }
+        Ok(())
+    }
+
+    pub fn chain_config(&self) -> Option<ChainConfig> {
+        ChainConfig::from_chain_id(self.chain_id)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainToken {
+    pub address: H160,
+    pub symbol: &'static str,
+    pub decimals: u8,
+    // storage slot of the balances mapping, used to seed balances without tracing
+    pub balance_slot: u32,
+}
+
+impl ChainToken {
+    fn new(address: &str, symbol: &'static str, decimals: u8, balance_slot: u32) -> Self {
+        Self { address: H160::from_str(address).unwrap(), symbol, decimals, balance_slot }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainConfig {
+    pub chain_id: U64,
+    pub wrapped_native: ChainToken,
+    pub stablecoins: Vec<ChainToken>,
+    pub multicall: H160,
+    pub block_time: Duration,
+}
+
+impl ChainConfig {
+    pub fn from_chain_id(chain_id: U64) -> Option<Self> {
+        let config = match chain_id.as_u64() {
+            // Ethereum mainnet
+            1 => Self {
+                chain_id,
+                wrapped_native: ChainToken::new(
+                    "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+                    "WETH",
+                    18,
+                    3,
+                ),
+                stablecoins: vec![
+                    ChainToken::new("0xdAC17F958D2ee523a2206206994597C13D831ec7", "USDT", 6, 2),
+                    ChainToken::new("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "USDC", 6, 9),
+                    ChainToken::new("0x6B175474E89094C44Da98b954EedeAC495271d0F", "DAI", 18, 2),
+                ],
+                multicall: *MULTICALL3_ADDRESS,
+                block_time: Duration::from_secs(12),
+            },
+            // BNB Smart Chain
+            56 => Self {
+                chain_id,
+                wrapped_native: ChainToken::new(
+                    "0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c",
+                    "WBNB",
+                    18,
+                    3,
+                ),
+                stablecoins: vec![
+                    ChainToken::new("0x55d398326f99059fF775485246999027B3197955", "USDT", 18, 1),
+                    ChainToken::new("0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56", "BUSD", 18, 1),
+                ],
+                multicall: *MULTICALL3_ADDRESS,
+                block_time: Duration::from_secs(3),
+            },
+            // Base
+            8453 => Self {
+                chain_id,
+                wrapped_native: ChainToken::new(
+                    "0x4200000000000000000000000000000000000006",
+                    "WETH",
+                    18,
+                    3,
+                ),
+                stablecoins: vec![ChainToken::new(
+                    "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913",
+                    "USDC",
+                    6,
+                    9,
+                )],
+                multicall: *MULTICALL3_ADDRESS,
+                block_time: Duration::from_secs(2),
+            },
+            // Arbitrum One
+            42161 => Self {
+                chain_id,
+                wrapped_native: ChainToken::new(
+                    "0x82aF49447D8a07e3bd95BD0d56f35241523fBab1",
+                    "WETH",
+                    18,
+                    51,
+                ),
+                stablecoins: vec![
+                    ChainToken::new("0xFd086bC7CD5C481DCC9C85ebE478A1C0b69FCbb9", "USDT", 6, 51),
+                    ChainToken::new("0xaf88d065e77c8cC2239327C5EDb3A432268e5831", "USDC", 6, 9),
+                ],
+                multicall: *MULTICALL3_ADDRESS,
+                block_time: Duration::from_millis(250),
+            },
+            _ => return None,
+        };
+
+        Some(config)
+    }
+
+    pub fn safe_tokens(&self) -> Vec<&ChainToken> {
+        std::iter::once(&self.wrapped_native).chain(self.stablecoins.iter()).collect()
+    }
+
+    pub fn get_token(&self, address: H160) -> Option<&ChainToken> {
+        self.safe_tokens().into_iter().find(|token| token.address == address)
     }
 }
 
@@ -0,0 +1,472 @@
+use anyhow::{anyhow, Result};
+use ethers::abi::{self, parse_abi, Token};
+use ethers::prelude::BaseContract;
+use ethers::types::{Bytes, H160, H256, U256, U64};
+use ethers_providers::{MockProvider, Provider};
+use foundry_common::types::{ToAlloy, ToEthers};
+use foundry_evm::revm::{db::EmptyDB, primitives::U256 as rU256};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::constants::{
+    ChainConfig, ChainToken, DEFAULT_GAS_LIMIT, DEFAULT_SENDER, MULTICALL3_ADDRESS, WEI,
+};
+use crate::honeypot::{HoneypotConfig, HoneypotFilter};
+use crate::simulator::{AccountOverride, BlockOverrides, EvmSimulator, StateOverride, Tx};
+
+// Build output of ../contracts (run `forge build` in ./contracts first), the mocks live in
+// ../contracts/src/mocks and are read from out/<Name>.sol/<Name>.json
+pub static ARTIFACTS_DIR: &str = "contracts/out";
+
+// Committed state fixtures, replayed by the tests without a provider
+pub static STATE_FIXTURES_DIR: &str = "tests/fixtures";
+
+// Liquidity of the fixture pair, 1 token is worth 0.0001 WETH
+pub static POOL_WETH_LIQUIDITY: u64 = 100;
+pub static POOL_TOKEN_LIQUIDITY: u64 = 1_000_000;
+
+// balanceOf of MockERC20 comes after name, symbol, decimals and totalSupply
+pub static MOCK_BALANCE_SLOT: u32 = 4;
+
+// An EvmSimulator on a pure in-memory state, nothing is ever fetched from the (mocked) provider
+pub type MemorySimulator = EvmSimulator<Provider<MockProvider>, EmptyDB>;
+
+pub fn memory_simulator(owner: H160) -> MemorySimulator {
+    memory_simulator_at(owner, U64::zero())
+}
+
+fn memory_simulator_at(owner: H160, block_number: U64) -> MemorySimulator {
+    let (provider, _) = Provider::mocked();
+    EvmSimulator::from_db(Arc::new(provider), owner, block_number, EmptyDB::default())
+}
+
+// The fork state fetched from the RPC during a simulation (see EvmSimulator::record_state)
+// Accounts use the eth_call override format, with `state` holding every fetched slot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateFixture {
+    pub chain_id: u64,
+    pub block_number: U64,
+    pub block: BlockOverrides,
+    pub accounts: StateOverride,
+    pub block_hashes: BTreeMap<u64, H256>,
+}
+
+impl StateFixture {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read state fixture {}: {e:?}", path.display()))?;
+        Ok(serde_json::from_str(&raw)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| anyhow!("Failed to write state fixture {}: {e:?}", path.display()))
+    }
+
+    // A simulator on exactly the recorded state, anything that was not recorded reads as empty
+    pub fn replay(&self, owner: H160) -> Result<MemorySimulator> {
+        let mut simulator = memory_simulator_at(owner, self.block_number);
+        simulator.evm.env.cfg.chain_id = self.chain_id;
+        simulator.apply_block_overrides(&self.block);
+        simulator.apply_state_override(&self.accounts)?;
+
+        let db = simulator.evm.db.as_mut().unwrap();
+        for (number, hash) in &self.block_hashes {
+            db.block_hashes.insert(rU256::from(*number), hash.to_alloy());
+        }
+
+        Ok(simulator)
+    }
+}
+
+// A chain whose wrapped native token is a MockERC20 deployed at `weth`
+pub fn mock_chain(weth: H160, chain_id: u64) -> ChainConfig {
+    ChainConfig {
+        chain_id: U64::from(chain_id),
+        wrapped_native: ChainToken {
+            address: weth,
+            symbol: "WETH",
+            decimals: 18,
+            balance_slot: MOCK_BALANCE_SLOT,
+        },
+        stablecoins: Vec::new(),
+        multicall: *MULTICALL3_ADDRESS,
+        block_time: Duration::from_secs(12),
+    }
+}
+
+// Creation bytecode of a compiled contract
+pub fn load_artifact(name: &str) -> Result<Bytes> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join(ARTIFACTS_DIR)
+        .join(format!("{name}.sol"))
+        .join(format!("{name}.json"));
+    let raw = fs::read_to_string(&path).map_err(|e| {
+        anyhow!("Failed to read {} (run `forge build` in ./contracts): {e:?}", path.display())
+    })?;
+
+    let artifact: serde_json::Value = serde_json::from_str(&raw)?;
+    let code = artifact["bytecode"]["object"]
+        .as_str()
+        .ok_or(anyhow!("{} has no bytecode.object", path.display()))?;
+    Ok(code.parse()?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockToken {
+    // plain ERC-20
+    Plain,
+    // keeps tax_bps of every buy and sell
+    Taxed { tax_bps: u64 },
+    // keeps tax_bps of every wallet to wallet transfer, swaps are free
+    TransferTaxed { tax_bps: u64 },
+    // blacklists every buyer, so nothing bought can be sold
+    Blacklist,
+    // rejects transfers above max_tx (in whole tokens)
+    MaxTx { max_tx: u64 },
+    // transfer, transferFrom and approve return nothing, like USDT
+    NoReturn,
+    // returns false instead of reverting when the balance or allowance is short
+    FalseReturn,
+}
+
+impl MockToken {
+    pub fn contract_name(&self) -> &'static str {
+        match self {
+            MockToken::Plain => "MockERC20",
+            MockToken::Taxed { .. } => "TaxedERC20",
+            MockToken::TransferTaxed { .. } => "TransferTaxERC20",
+            MockToken::Blacklist => "BlacklistERC20",
+            MockToken::MaxTx { .. } => "MaxTxERC20",
+            MockToken::NoReturn => "NoReturnERC20",
+            MockToken::FalseReturn => "FalseReturnERC20",
+        }
+    }
+
+    fn constructor_args(&self, decimals: u8) -> Vec<Token> {
+        let mut args = vec![
+            Token::String("Mock Token".to_string()),
+            Token::String("MOCK".to_string()),
+            Token::Uint(U256::from(decimals)),
+        ];
+        match self {
+            MockToken::Taxed { tax_bps } | MockToken::TransferTaxed { tax_bps } => {
+                args.push(Token::Uint(U256::from(*tax_bps)))
+            }
+            MockToken::MaxTx { max_tx } => args
+                .push(Token::Uint(U256::from(*max_tx) * U256::from(10).pow(U256::from(decimals)))),
+            MockToken::Plain
+            | MockToken::Blacklist
+            | MockToken::NoReturn
+            | MockToken::FalseReturn => {}
+        }
+        args
+    }
+}
+
+// A WETH/token UniswapV2 pair deployed into memory, with the simulator contract ready to swap on it
+pub struct Fixture {
+    pub simulator: MemorySimulator,
+    pub weth: H160,
+    pub token: H160,
+    pub pair: H160,
+    mocks: BaseContract,
+}
+
+impl Fixture {
+    pub fn new(kind: MockToken) -> Result<Self> {
+        let mut simulator = memory_simulator(*DEFAULT_SENDER);
+        simulator.set_eth_balance(1000);
+        simulator.deploy_simulator();
+
+        let mocks = BaseContract::from(parse_abi(&[
+            "function mint(address,uint256) external",
+            "function setPair(address) external",
+            "function sync() external",
+        ])?);
+
+        let weth = deploy(
+            &mut simulator,
+            "MockERC20",
+            &[
+                Token::String("Wrapped Ether".to_string()),
+                Token::String("WETH".to_string()),
+                Token::Uint(U256::from(18)),
+            ],
+        )?;
+        let token = deploy(&mut simulator, kind.contract_name(), &kind.constructor_args(18))?;
+        let pair = deploy(
+            &mut simulator,
+            "MockUniswapV2Pair",
+            &[Token::Address(weth), Token::Address(token)],
+        )?;
+
+        let mut fixture = Self { simulator, weth, token, pair, mocks };
+        fixture.send(token, fixture.mocks.encode("setPair", pair)?)?;
+        fixture.mint(weth, pair, U256::from(POOL_WETH_LIQUIDITY) * *WEI)?;
+        fixture.mint(token, pair, U256::from(POOL_TOKEN_LIQUIDITY) * *WEI)?;
+        fixture.send(pair, fixture.mocks.encode("sync", ())?)?;
+
+        Ok(fixture)
+    }
+
+    // Mints any of the mock tokens, WETH included
+    pub fn mint(&mut self, token: H160, to: H160, amount: U256) -> Result<()> {
+        let calldata = self.mocks.encode("mint", (to, amount))?;
+        self.send(token, calldata)
+    }
+
+    // Buys with `amount_in` WETH and sells everything received back through the simulator contract
+    // Returns the (expected, actual) outputs of both swaps, as v2_simulate_swap reports them
+    pub fn round_trip(&mut self, amount_in: U256) -> Result<((U256, U256), (U256, U256))> {
+        let simulator_address = self.simulator.simulator_address;
+        self.mint(self.weth, simulator_address, amount_in)?;
+
+        let buy =
+            self.simulator.v2_simulate_swap(amount_in, self.pair, self.weth, self.token, true)?;
+        let sell =
+            self.simulator.v2_simulate_swap(buy.1, self.pair, self.token, self.weth, true)?;
+        Ok((buy, sell))
+    }
+
+    // Dumps the whole in-memory state in the format of EvmSimulator::record_state, so a fixture
+    // can be saved and replayed like a recorded fork
+    pub fn record_state(&self) -> Result<StateFixture> {
+        let db = self.simulator.evm.db.as_ref().unwrap();
+
+        let mut accounts = StateOverride::new();
+        for (address, account) in &db.accounts {
+            let code = account
+                .info
+                .code
+                .clone()
+                .or_else(|| db.contracts.get(&account.info.code_hash).cloned())
+                .map(|code| code.original_bytes())
+                .filter(|code| !code.is_empty())
+                .map(|code| code.to_vec().into());
+            let state = account
+                .storage
+                .iter()
+                .map(|(slot, value)| {
+                    (H256::from(slot.to_be_bytes::<32>()), H256::from(value.to_be_bytes::<32>()))
+                })
+                .collect();
+            accounts.insert(
+                address.to_ethers(),
+                AccountOverride {
+                    balance: Some(account.info.balance.to_ethers()),
+                    nonce: Some(U64::from(account.info.nonce)),
+                    code,
+                    state: Some(state),
+                    state_diff: None,
+                },
+            );
+        }
+
+        Ok(StateFixture {
+            chain_id: self.simulator.evm.env.cfg.chain_id,
+            block_number: self.simulator.block_number,
+            block: self.simulator.current_block_overrides(),
+            accounts,
+            block_hashes: BTreeMap::new(),
+        })
+    }
+
+    // The fixture's chain: its WETH mock stands in for the wrapped native token
+    pub fn chain(&self) -> ChainConfig {
+        mock_chain(self.weth, self.simulator.evm.env.cfg.chain_id)
+    }
+
+    // A HoneypotFilter running on the fixture, seeding and swapping the mock WETH
+    pub fn honeypot_filter(
+        self,
+        config: HoneypotConfig,
+    ) -> HoneypotFilter<Provider<MockProvider>, EmptyDB> {
+        let chain = self.chain();
+        HoneypotFilter::with_simulator(self.simulator, chain, config)
+    }
+
+    fn send(&mut self, to: H160, calldata: Bytes) -> Result<()> {
+        self.simulator.call(Tx {
+            caller: self.simulator.owner,
+            transact_to: to,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        Ok(())
+    }
+}
+
+fn deploy(simulator: &mut MemorySimulator, name: &str, args: &[Token]) -> Result<H160> {
+    let mut init_code = load_artifact(name)?.to_vec();
+    init_code.extend(abi::encode(args));
+    simulator.deploy(init_code.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pools::{DexVariant, Pool};
+    use ethers::utils::get_contract_address;
+
+    async fn simulate_swap(kind: MockToken) -> (bool, f64, f64) {
+        let fixture = Fixture::new(kind).unwrap();
+        let (token, pair) = (fixture.token, fixture.pair);
+        let mut filter = fixture.honeypot_filter(HoneypotConfig::default());
+        filter.simulate_swap(token, pair).await.unwrap()
+    }
+
+    // Fixture::new deploys WETH, the token and the pair with the owner's nonces 0, 1 and 2
+    fn mock_addresses() -> (H160, H160, H160) {
+        let address = |nonce: u64| get_contract_address(*DEFAULT_SENDER, nonce);
+        (address(0), address(1), address(2))
+    }
+
+    // Replays a saved taxed fixture through a fresh filter, no artifact or provider needed
+    async fn replay_taxed_fixture(path: &Path) {
+        let (weth, token, pair) = mock_addresses();
+        let state = StateFixture::load(path).unwrap();
+        let chain = mock_chain(weth, state.chain_id);
+
+        let simulator = state.replay(*DEFAULT_SENDER).unwrap();
+        let mut filter =
+            HoneypotFilter::with_simulator(simulator, chain, HoneypotConfig::default());
+        let (swappable, buy_tax, sell_tax) = filter.simulate_swap(token, pair).await.unwrap();
+        assert!(swappable);
+        assert!((buy_tax - 0.05).abs() < 0.001, "buy tax {buy_tax}");
+        assert!((sell_tax - 0.05).abs() < 0.002, "sell tax {sell_tax}");
+    }
+
+    fn taxed_fixture_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(STATE_FIXTURES_DIR).join("taxed_erc20.json")
+    }
+
+    #[tokio::test]
+    async fn committed_fixture_replays() {
+        replay_taxed_fixture(&taxed_fixture_path()).await;
+    }
+
+    #[tokio::test]
+    async fn recorded_fixture_replays() {
+        let path = std::env::temp_dir().join(format!("taxed_erc20_{}.json", std::process::id()));
+        let fixture = Fixture::new(MockToken::Taxed { tax_bps: 500 }).unwrap();
+        assert_eq!((fixture.weth, fixture.token, fixture.pair), mock_addresses());
+        fixture.record_state().unwrap().save(&path).unwrap();
+
+        replay_taxed_fixture(&path).await;
+        fs::remove_file(&path).unwrap();
+    }
+
+    // Regenerates the committed fixture after changing the mocks (needs `forge build`):
+    // cargo test record_taxed_fixture -- --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn record_taxed_fixture() {
+        let path = taxed_fixture_path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let fixture = Fixture::new(MockToken::Taxed { tax_bps: 500 }).unwrap();
+        fixture.record_state().unwrap().save(&path).unwrap();
+
+        replay_taxed_fixture(&path).await;
+    }
+
+    #[test]
+    fn plain_round_trip() {
+        let mut fixture = Fixture::new(MockToken::Plain).unwrap();
+        let (buy, sell) = fixture.round_trip(*WEI).unwrap();
+        assert!(!buy.0.is_zero());
+        assert_eq!(buy.0, buy.1);
+        assert_eq!(sell.0, sell.1);
+        // the pool fee is paid on both legs
+        assert!(sell.1 < *WEI);
+    }
+
+    #[test]
+    fn chain_uses_the_mock_weth() {
+        let fixture = Fixture::new(MockToken::Plain).unwrap();
+        let chain = fixture.chain();
+        assert_eq!(chain.wrapped_native.address, fixture.weth);
+        assert_eq!(chain.wrapped_native.balance_slot, MOCK_BALANCE_SLOT);
+    }
+
+    #[tokio::test]
+    async fn plain_token_is_swappable() {
+        let (swappable, buy_tax, sell_tax) = simulate_swap(MockToken::Plain).await;
+        assert!(swappable);
+        assert_eq!(buy_tax, 0.0);
+        assert_eq!(sell_tax, 0.0);
+    }
+
+    #[tokio::test]
+    async fn taxed_token_reports_its_tax() {
+        let (swappable, buy_tax, sell_tax) = simulate_swap(MockToken::Taxed { tax_bps: 500 }).await;
+        assert!(swappable);
+        assert!((buy_tax - 0.05).abs() < 0.001, "buy tax {buy_tax}");
+        assert!((sell_tax - 0.05).abs() < 0.002, "sell tax {sell_tax}");
+    }
+
+    #[tokio::test]
+    async fn blacklist_token_cannot_be_sold() {
+        let (swappable, ..) = simulate_swap(MockToken::Blacklist).await;
+        assert!(!swappable);
+    }
+
+    #[tokio::test]
+    async fn max_tx_token_cannot_be_bought() {
+        let (swappable, buy_tax, sell_tax) = simulate_swap(MockToken::MaxTx { max_tx: 10 }).await;
+        assert!(!swappable);
+        assert_eq!((buy_tax, sell_tax), (0.0, 0.0));
+    }
+
+    // Runs filter_tokens on the fixture pair, with the mock WETH known the way setup() knows it
+    async fn filter_token(
+        kind: MockToken,
+    ) -> (H160, HoneypotFilter<Provider<MockProvider>, EmptyDB>) {
+        let fixture = Fixture::new(kind).unwrap();
+        let (weth, token, pair) = (fixture.weth, fixture.token, fixture.pair);
+        let mut filter = fixture.honeypot_filter(HoneypotConfig::default());
+        filter.balance_slots.insert(weth, MOCK_BALANCE_SLOT);
+        filter.safe_token_info.insert(
+            weth,
+            crate::tokens::Token {
+                address: weth,
+                implementation: None,
+                name: "Wrapped Ether".to_string(),
+                symbol: "WETH".to_string(),
+                decimals: 18,
+            },
+        );
+
+        let pool = Pool {
+            address: pair,
+            version: DexVariant::UniswapV2,
+            token0: weth,
+            token1: token,
+            decimals0: 18,
+            decimals1: 18,
+            fee: 300,
+        };
+        filter.filter_tokens(&vec![pool]).await.unwrap();
+        (token, filter)
+    }
+
+    #[tokio::test]
+    async fn filter_rejects_taxed_wallet_transfers() {
+        let (token, filter) = filter_token(MockToken::TransferTaxed { tax_bps: 2000 }).await;
+        // the swaps went through untaxed, the wallet transfer tax alone rejects the token
+        assert_eq!(filter.get_tax_rate(token), (0.0, 0.0));
+        assert!(filter.is_honeypot(token));
+    }
+}
@@ -1,33 +1,124 @@
 use alloy_primitives::{Address, U160};
 use anyhow::{anyhow, Result};
-use ethers::types::{Block, BlockId, BlockNumber, H160, H256, U256, U64};
+use ethers::types::{Block, BlockId, BlockNumber, H160, H256, U256};
 use ethers_providers::Middleware;
+use foundry_evm::{fork::SharedBackend, revm::db::DatabaseRef};
 use log::info;
-use std::ops::Sub;
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, str::FromStr, sync::Arc};
 
-use crate::constants::{WETH_BALANCE_SLOT, WETH_DECIMALS};
+use crate::compliance::{ComplianceCheck, ComplianceReport};
+use crate::constants::{ChainConfig, TRANSFER_RECIPIENT};
 use crate::pools::Pool;
+use crate::risk::{
+    find_selectors, Liquidity, RiskScore, RiskSignals, RiskThresholds, RISKY_SIGNATURES,
+};
 use crate::simulator::{EvmSimulator, SimpleTransferError, SwapError};
-use crate::tokens::{get_implementation, get_token_info, Token};
+use crate::tax::{Anomaly, TaxDelta};
+use crate::tokens::{get_deployment_block, get_implementation, get_token_info, Token};
 use crate::trace::EvmTracer;
+use crate::transfer::TransferAnalysis;
+
+// How the filter tests tokens and what it accepts, the [honeypot] section of the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HoneypotConfig {
+    // wrapped native token swapped into WETH pools, in whole units
+    pub weth_swap_amount: f64,
+    // stablecoins swapped into stablecoin pools, in whole units
+    pub stable_swap_amount: u32,
+    // tokens sent by the transfer and compliance tests, in whole units
+    pub transfer_test_amount: u32,
+    // proxies can swap their logic at any time, filter_tokens rejects them without testing
+    pub reject_proxies: bool,
+    // also buy and sell through the V2 router of the pool's factory in check_token
+    pub check_router: bool,
+    // tax and liquidity limits, shared with the risk score
+    #[serde(flatten)]
+    pub thresholds: RiskThresholds,
+    // factory -> router, taken from the factory registry rather than this section
+    #[serde(skip)]
+    pub routers: HashMap<H160, H160>,
+}
+
+impl Default for HoneypotConfig {
+    fn default() -> Self {
+        Self {
+            weth_swap_amount: 0.1,
+            stable_swap_amount: 10000,
+            transfer_test_amount: 10000,
+            reject_proxies: true,
+            check_router: false,
+            thresholds: RiskThresholds::default(),
+            routers: HashMap::new(),
+        }
+    }
+}
+
+impl HoneypotConfig {
+    // The WETH swap amount in wei, with the whole units to seed so that it is covered
+    pub fn weth_swap_amount(&self, decimals: u8) -> (U256, u32) {
+        let amount = U256::from((self.weth_swap_amount * 10f64.powi(decimals as i32)) as u128);
+        (amount, self.weth_swap_amount.ceil().max(1.0) as u32)
+    }
 
-const WETH_SWAP_AMOUNT: f64 = 0.1;
-const TAX_CRITERIA: f64 = 0.1;
+    // The amount sent by the transfer and compliance tests, in the token's smallest unit
+    pub fn transfer_test_amount(&self, decimals: u8) -> U256 {
+        U256::from(self.transfer_test_amount) * U256::from(10).pow(U256::from(decimals))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct SafeTokens {
+    // wrapped native token of the chain (WETH, WBNB, ...)
     pub weth: H160,
+    pub stablecoins: Vec<H160>,
 }
 
 impl SafeTokens {
-    pub fn new() -> Self {
-        Self { weth: H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap() }
+    pub fn new(chain: &ChainConfig) -> Self {
+        Self {
+            weth: chain.wrapped_native.address,
+            stablecoins: chain.stablecoins.iter().map(|token| token.address).collect(),
+        }
+    }
+
+    pub fn all(&self) -> Vec<H160> {
+        std::iter::once(self.weth).chain(self.stablecoins.iter().copied()).collect()
     }
 }
 
-pub struct HoneypotFilter<M> {
-    pub simulator: EvmSimulator<M>,
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenCheck {
+    pub token: H160,
+    pub pool: H160,
+    pub is_proxy: bool,
+    pub owner: Option<H160>,
+    pub swappable: bool,
+    pub buy_tax: f64,
+    pub sell_tax: f64,
+    pub transferable: bool,
+    pub transfer_tax: f64,
+    pub transfer_analysis: Option<TransferAnalysis>,
+    // Only filled when the filter is asked to also go through the router
+    pub router_swap: Option<RouterSwapCheck>,
+    pub compliance: Option<ComplianceReport>,
+    pub anomalies: Vec<Anomaly>,
+    pub risk: Option<RiskScore>,
+}
+
+// Buy/sell through the UniswapV2 router with native ETH, the way most users trade
+#[derive(Debug, Clone, Serialize)]
+pub struct RouterSwapCheck {
+    pub router: H160,
+    pub swappable: bool,
+    pub buy_tax: f64,
+    pub sell_tax: f64,
+}
+
+pub struct HoneypotFilter<M, DB: DatabaseRef = SharedBackend> {
+    pub simulator: EvmSimulator<M, DB>,
+    pub chain: ChainConfig,
     pub safe_tokens: SafeTokens,
     pub token_info: HashMap<H160, Token>,
     pub safe_token_info: HashMap<H160, Token>,
@@ -36,13 +127,76 @@ pub struct HoneypotFilter<M> {
     buy_tax: HashMap<H160, f64>,
     sell_tax: HashMap<H160, f64>,
     is_proxy: HashMap<H160, bool>,
+    // Non-tax oddities seen while testing, reported next to the verdict
+    pub anomalies: HashMap<H160, Vec<Anomaly>>,
+    pub config: HoneypotConfig,
+}
+
+// An independent copy, so a check can run without holding on to the shared filter
+impl<M, DB: DatabaseRef + Clone> Clone for HoneypotFilter<M, DB> {
+    fn clone(&self) -> Self {
+        Self {
+            simulator: self.simulator.clone(),
+            chain: self.chain.clone(),
+            safe_tokens: self.safe_tokens.clone(),
+            token_info: self.token_info.clone(),
+            safe_token_info: self.safe_token_info.clone(),
+            balance_slots: self.balance_slots.clone(),
+            honeypot: self.honeypot.clone(),
+            buy_tax: self.buy_tax.clone(),
+            sell_tax: self.sell_tax.clone(),
+            is_proxy: self.is_proxy.clone(),
+            anomalies: self.anomalies.clone(),
+            config: self.config.clone(),
+        }
+    }
 }
 
 impl<M: Middleware + 'static> HoneypotFilter<M> {
-    pub fn new(provider: Arc<M>, block: Block<H256>) -> Self {
+    pub fn new(
+        provider: Arc<M>,
+        block: Block<H256>,
+        chain: ChainConfig,
+        config: HoneypotConfig,
+    ) -> Self {
         let owner = H160::from_str("0x001a06BF8cE4afdb3f5618f6bafe35e9Fc09F187").unwrap();
-        let simulator = EvmSimulator::new(provider.clone(), owner, block.number.unwrap());
-        let safe_tokens = SafeTokens::new();
+        let mut simulator = EvmSimulator::new(provider.clone(), owner, block.number.unwrap());
+        simulator.evm.env.cfg.chain_id = chain.chain_id.as_u64();
+        simulator.apply_next_header(&block, chain.block_time);
+        Self::with_simulator(simulator, chain, config)
+    }
+
+    // Creates a filter forked at the latest block with the safe tokens already set up
+    pub async fn from_latest_block(
+        provider: Arc<M>,
+        chain: ChainConfig,
+        config: HoneypotConfig,
+    ) -> Result<Self> {
+        let block = provider
+            .get_block(BlockNumber::Latest)
+            .await
+            .map_err(|e| anyhow!("Failed to fetch the latest block: {e:?}"))?
+            .ok_or(anyhow!("Latest block not found"))?;
+
+        let mut filter = Self::new(provider, block, chain, config);
+        filter.setup().await;
+        Ok(filter)
+    }
+}
+
+impl<M, DB> HoneypotFilter<M, DB>
+where
+    M: Middleware + 'static,
+    DB: DatabaseRef + Clone,
+    DB::Error: std::error::Error + Send + Sync + 'static,
+{
+    // Runs the checks on an existing simulator, e.g. one replayed from a StateFixture
+    pub fn with_simulator(
+        simulator: EvmSimulator<M, DB>,
+        chain: ChainConfig,
+        config: HoneypotConfig,
+    ) -> Self {
+        let safe_tokens = SafeTokens::new(&chain);
         let token_info = HashMap::new();
         let safe_token_info = HashMap::new();
         let balance_slots = HashMap::new();
@@ -50,8 +204,10 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
         let buy_tax = HashMap::new();
         let sell_tax = HashMap::new();
         let is_proxy = HashMap::new();
+        let anomalies = HashMap::new();
         Self {
             simulator,
+            chain,
             safe_tokens,
             token_info,
             safe_token_info,
@@ -60,18 +216,38 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
             buy_tax,
             sell_tax,
             is_proxy,
+            anomalies,
+            config,
+        }
+    }
+
+    // Forgets the per-token verdicts, e.g. after the simulator moved to a new block
+    pub fn reset_results(&mut self) {
+        self.token_info.clear();
+        self.honeypot.clear();
+        self.buy_tax.clear();
+        self.sell_tax.clear();
+        self.is_proxy.clear();
+        self.anomalies.clear();
+    }
+
+    fn record_anomaly(&mut self, token: H160, anomaly: Anomaly) {
+        let anomalies = self.anomalies.entry(token).or_default();
+        if !anomalies.contains(&anomaly) {
+            anomalies.push(anomaly);
         }
     }
 
     pub async fn setup(&mut self) {
-        // Get safe_token_info using the four following tokens that are widely used as safe tokens
+        // Get safe_token_info using the wrapped native token and the stablecoins of the chain
         let provider = &self.simulator.provider;
         let owner = self.simulator.owner;
         let block_number = &self.simulator.block_number;
+        let multicall = Some(self.chain.multicall);
 
         let tracer = EvmTracer::new(provider.clone());
 
-        let chain_id = provider.get_chainid().await.unwrap();
+        let chain_id = self.chain.chain_id;
         let nonce = self
             .simulator
             .provider
@@ -79,23 +255,25 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
             .await
             .unwrap();
 
-        for token in [self.safe_tokens.weth] {
+        for token in self.safe_tokens.all() {
             if let std::collections::hash_map::Entry::Vacant(e) = self.safe_token_info.entry(token)
             {
-                match tracer
-                    .find_balance_slot(
-                        token,
-                        owner,
-                        nonce,
-                        U64::from(chain_id.as_u64()),
-                        block_number.as_u64(),
-                    )
-                    .await
-                {
+                // Use the known balance slot of the chain config before falling back to tracing
+                let known_slot = self.chain.get_token(token).map(|info| (true, info.balance_slot));
+                let slot = match known_slot {
+                    Some(slot) => Ok(slot),
+                    None => {
+                        tracer
+                            .find_balance_slot(token, owner, nonce, chain_id, block_number.as_u64())
+                            .await
+                    }
+                };
+                match slot {
                     Ok(slot) => {
                         if slot.0 {
                             self.balance_slots.insert(token, slot.1);
-                            let mut info = get_token_info(provider.clone(), token).await.unwrap();
+                            let mut info =
+                                get_token_info(provider.clone(), token, multicall).await.unwrap();
                             match get_implementation(provider.clone(), token, *block_number).await {
                                 Ok(implementation) => info.add_implementation(implementation),
                                 Err(_) => {}
@@ -113,9 +291,12 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
     pub async fn simulate_transfer(&mut self, token_addr: H160) -> Result<(bool, f64)> {
         self.simulator.deploy_simulator();
 
-        let simulate_transfer_res = self.simulator.simulate_simple_transfer(token_addr).await;
+        let simulate_transfer_res = self
+            .simulator
+            .simulate_simple_transfer(token_addr, self.config.transfer_test_amount)
+            .await;
         match simulate_transfer_res {
-            Ok(tax_rate) => Ok((true, tax_rate.as_u64() as f64 / 100.0)),
+            Ok(tax_bps) => Ok((true, tax_bps as f64 / 10000.0)),
             Err(e) => match e.downcast_ref::<SimpleTransferError>() {
                 Some(SimpleTransferError::TxFailed(_)) => {
                     info!("<Transfer ERROR>: {:?}", e);
@@ -129,6 +310,24 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
         }
     }
 
+    // Transfer to a fresh wallet, with the tax in basis points and where it went
+    pub async fn analyze_transfer(&mut self, token: H160) -> Result<TransferAnalysis> {
+        let info =
+            get_token_info(self.simulator.provider.clone(), token, Some(self.chain.multicall))
+                .await?;
+        let amount = self.config.transfer_test_amount(info.decimals);
+        self.simulator.analyze_transfer(token, *TRANSFER_RECIPIENT, amount).await
+    }
+
+    // ERC-20 compliance suite (transfer, approve + transferFrom, allowance, zero and self transfers)
+    pub async fn check_compliance(&mut self, token: H160) -> Result<ComplianceReport> {
+        let info =
+            get_token_info(self.simulator.provider.clone(), token, Some(self.chain.multicall))
+                .await?;
+        let amount = self.config.transfer_test_amount(info.decimals);
+        self.simulator.check_erc20_compliance(token, amount).await
+    }
+
     // FIXME
     pub async fn is_proxy(&mut self, token_addr: H160) -> bool {
         self.simulator.is_proxy(Address::from(U160::from_be_bytes(token_addr.0)))
@@ -143,20 +342,18 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
         self.simulator.deploy_simulator();
 
         // seed the simulator with some safe token balance
-        let safe_token = self.safe_tokens.weth;
+        let weth = self.chain.wrapped_native.clone();
+        let safe_token = weth.address;
 
-        let amount_in_u32 = 1u32;
+        let (weth_amount_in, amount_in_u32) = self.config.weth_swap_amount(weth.decimals);
         self.simulator.set_token_balance(
             self.simulator.simulator_address,
             safe_token,
-            WETH_DECIMALS,
-            WETH_BALANCE_SLOT,
+            weth.decimals,
+            weth.balance_slot,
             amount_in_u32,
         )?;
 
-        // buy with 0.1WETH
-        let weth_amount_in = U256::from(10i64.pow(WETH_DECIMALS.sub(1).into()));
-
         // Buy Test
         let swap_res = self.simulator.v2_simulate_swap(
             weth_amount_in,
@@ -181,13 +378,14 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
             },
         };
 
-        let out_ratio = expected_amount_out.checked_sub(actual_amount_out).unwrap();
-        let buy_tax_rate = out_ratio
-            .checked_mul(U256::from(10000))
-            .unwrap()
-            .checked_div(expected_amount_out)
-            .ok_or(anyhow!("Divide by zero"))?;
-        let buy_tax_rate_f64 = buy_tax_rate.as_u64() as f64 / 10000.0;
+        let buy = TaxDelta::new(expected_amount_out, actual_amount_out);
+        if let Some(anomaly) = buy.anomaly(true) {
+            self.record_anomaly(token_addr, anomaly);
+        }
+        if !buy.is_measurable() {
+            return Ok((false, 0.0, 0.0));
+        }
+        let buy_tax_rate_f64 = buy.tax_rate();
 
         // Sell Test
         // Use out.1 as an amountIn arg for the sell swap so as to avoit calling set_token_balance
@@ -211,17 +409,97 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
             },
         };
 
-        let out_ratio = expected_amount_out.checked_sub(actual_amount_out).unwrap();
-        let sell_tax_rate = out_ratio
-            .checked_mul(U256::from(10000))
-            .unwrap()
-            .checked_div(expected_amount_out)
-            .ok_or(anyhow!("Divide by zero"))?;
-        let sell_tax_rate_f64 = sell_tax_rate.as_u64() as f64 / 10000.0;
+        let sell = TaxDelta::new(expected_amount_out, actual_amount_out);
+        if let Some(anomaly) = sell.anomaly(false) {
+            self.record_anomaly(token_addr, anomaly);
+        }
+        if !sell.is_measurable() {
+            return Ok((false, buy_tax_rate_f64, 0.0));
+        }
+        let sell_tax_rate_f64 = sell.tax_rate();
 
         Ok((true, buy_tax_rate_f64, sell_tax_rate_f64))
     }
 
+    // Buy with native ETH and sell back through the V2 router of the pool's factory, so that the
+    // token sees the router as msg.sender the same way it does for regular users
+    pub async fn simulate_router_swap(
+        &mut self,
+        token_addr: H160,
+        pool_addr: H160,
+    ) -> Result<RouterSwapCheck> {
+        let weth = self.chain.wrapped_native.clone();
+        let factory = self.simulator.v2_pool_factory(pool_addr)?;
+        let router = *self
+            .config
+            .routers
+            .get(&factory)
+            .ok_or(anyhow!("No V2 router configured for factory {factory:?}"))?;
+
+        // The router picks its pair through its own factory, it has to be the pool under test
+        let router_factory = self.simulator.v2_router_factory(router)?;
+        let router_pair =
+            self.simulator.v2_factory_get_pair(router_factory, weth.address, token_addr)?;
+        if router_pair != pool_addr {
+            return Err(anyhow!(
+                "Router {router:?} trades {token_addr:?} through {router_pair:?}, not {pool_addr:?}"
+            ));
+        }
+
+        let mut check = RouterSwapCheck { router, swappable: false, buy_tax: 0.0, sell_tax: 0.0 };
+
+        let (eth_amount_in, eth_balance) = self.config.weth_swap_amount(weth.decimals);
+        self.simulator.set_eth_balance(eth_balance);
+
+        // Buy Test
+        let buy_res = self.simulator.v2_router_buy(router, weth.address, token_addr, eth_amount_in);
+        let (expected_amount_out, actual_amount_out) = match buy_res {
+            Ok(out) => out,
+            Err(e) => match e.downcast_ref::<SwapError>() {
+                Some(SwapError::TxFailed(_)) => {
+                    info!("<ROUTER BUY ERROR>: {:?}", e);
+                    return Ok(check);
+                }
+                _ => return Err(e),
+            },
+        };
+
+        let buy = TaxDelta::new(expected_amount_out, actual_amount_out);
+        if let Some(anomaly) = buy.anomaly(true) {
+            self.record_anomaly(token_addr, anomaly);
+        }
+        if !buy.is_measurable() {
+            return Ok(check);
+        }
+        check.buy_tax = buy.tax_rate();
+
+        // Sell Test
+        let sell_res =
+            self.simulator.v2_router_sell(router, weth.address, token_addr, actual_amount_out);
+        let (expected_amount_out, actual_amount_out) = match sell_res {
+            Ok(out) => out,
+            Err(e) => match e.downcast_ref::<SwapError>() {
+                Some(SwapError::TxFailed(_)) => {
+                    info!("<ROUTER SELL ERROR>: {:?}", e);
+                    return Ok(check);
+                }
+                _ => return Err(e),
+            },
+        };
+
+        let sell = TaxDelta::new(expected_amount_out, actual_amount_out);
+        if let Some(anomaly) = sell.anomaly(false) {
+            self.record_anomaly(token_addr, anomaly);
+        }
+        if !sell.is_measurable() {
+            return Ok(check);
+        }
+        check.sell_tax = sell.tax_rate();
+        check.swappable = true;
+
+        Ok(check)
+    }
+
     pub async fn filter_tokens(&mut self, pools: &Vec<Pool>) -> Result<()> {
         self.simulator.deploy_simulator();
 
@@ -255,26 +533,39 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
                     self.simulator.is_proxy(Address::from(U160::from_be_bytes(test_token.0)));
                 if is_proxy_contr {
                     info!("⚠️ [{}] {} is proxy", idx, test_token);
-                    self.honeypot.insert(test_token, true);
                     self.is_proxy.insert(test_token, true);
-                    continue;
+                    if self.config.reject_proxies {
+                        self.honeypot.insert(test_token, true);
+                        continue;
+                    }
                 }
 
                 // We take extra measures to filter out the pools with too little liquidity
                 // Using the below amount to test swaps, we know that there's enough liquidity in the pool
-                let amount_in_u32 = 1;
-                let mut amount_in_f64 = 1.0;
+                let mut amount_in_u32 = 1;
+                let mut amount_in_weth = U256::zero();
 
                 if safe_token == self.safe_tokens.weth {
-                    amount_in_f64 = WETH_SWAP_AMOUNT;
+                    (amount_in_weth, amount_in_u32) =
+                        self.config.weth_swap_amount(self.chain.wrapped_native.decimals);
+                } else if self.safe_tokens.stablecoins.contains(&safe_token) {
+                    amount_in_u32 = self.config.stable_swap_amount;
+                }
+
+                // Not a honeypot, but too shallow to trade: another pool of the token may do
+                // Reserves that can't be read fail the check, the swaps below would fail as well
+                match self.pool_liquidity(pool.address, test_token) {
+                    Ok(liquidity) if self.config.thresholds.is_shallow(liquidity) => {
+                        info!("⚠️ [{}] {} only has {:?}", idx, pool.address, liquidity);
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        info!("<RESERVES ERROR> {} {:?}", pool.address, e);
+                        self.honeypot.insert(test_token, true);
+                        continue;
+                    }
                 }
-                // else if safe_token == self.safe_tokens.usdt {
-                // amount_in_u32 = 10000;
-                // } else if safe_token == self.safe_tokens.usdc {
-                // amount_in_u32 = 10000;
-                // } else if safe_token == self.safe_tokens.dai {
-                // amount_in_u32 = 10000
-                // }
 
                 // seed the simulator with some safe token balance
                 let safe_token_info = self.safe_token_info.get(&safe_token).unwrap();
@@ -291,7 +582,7 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
                 info!("✅ [{}] {} -> {:?}", idx, safe_token_info.symbol, test_token);
 
                 let amount_in = if safe_token == self.safe_tokens.weth {
-                    U256::from((amount_in_f64 * 10f64.powi(18)) as u64)
+                    amount_in_weth
                 } else {
                     U256::from(amount_in_u32)
                         .checked_mul(U256::from(10).pow(U256::from(safe_token_info.decimals)))
@@ -315,13 +606,19 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
                     }
                 };
 
-                let out_ratio = out.0.checked_sub(out.1).unwrap();
-                let buy_tax_rate =
-                    out_ratio.checked_mul(U256::from(10000)).unwrap().checked_div(out.0).unwrap();
-                let buy_tax_rate = buy_tax_rate.as_u64() as f64 / 10000.0;
+                let buy = TaxDelta::new(out.0, out.1);
+                if let Some(anomaly) = buy.anomaly(true) {
+                    self.record_anomaly(test_token, anomaly);
+                }
+                if !buy.is_measurable() {
+                    info!("<BUY ERROR> {:?} pool math predicts no output", test_token);
+                    self.honeypot.insert(test_token, true);
+                    continue;
+                }
+                let buy_tax_rate = buy.tax_rate();
                 self.buy_tax.insert(test_token, buy_tax_rate);
 
-                if buy_tax_rate < TAX_CRITERIA {
+                if buy_tax_rate < self.config.thresholds.max_buy_tax {
                     // Sell Test
                     let amount_in = out.1;
                     let sell_output = self.simulator.v2_simulate_swap(
@@ -340,17 +637,103 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
                         }
                     };
 
-                    let out_ratio = out.0.checked_sub(out.1).unwrap();
-                    let sell_tax_rate = out_ratio
-                        .checked_mul(U256::from(10000))
-                        .unwrap()
-                        .checked_div(out.0)
-                        .unwrap();
-                    let sell_tax_rate = sell_tax_rate.as_u64() as f64 / 10000.0;
+                    let sell = TaxDelta::new(out.0, out.1);
+                    if let Some(anomaly) = sell.anomaly(false) {
+                        self.record_anomaly(test_token, anomaly);
+                    }
+                    if !sell.is_measurable() {
+                        info!("<SELL ERROR> {:?} pool math predicts no output", test_token);
+                        self.honeypot.insert(test_token, true);
+                        continue;
+                    }
+                    let sell_tax_rate = sell.tax_rate();
                     self.sell_tax.insert(test_token, sell_tax_rate);
 
-                    if sell_tax_rate < TAX_CRITERIA {
-                        match get_token_info(self.simulator.provider.clone(), test_token).await {
+                    if sell_tax_rate < self.config.thresholds.max_sell_tax {
+                        // decimals() is optional in ERC-20, most tokens without it use 18
+                        let decimals = self.simulator.token_decimals(test_token).unwrap_or(18);
+                        let amount = self.config.transfer_test_amount(decimals);
+
+                        // Routers sell through transferFrom, which the swaps above never use
+                        let compliance = match self
+                            .simulator
+                            .check_erc20_compliance(test_token, amount)
+                            .await
+                        {
+                            Ok(report) => {
+                                let failed = report.failed();
+                                if failed.contains(&ComplianceCheck::Transfer)
+                                    || failed.contains(&ComplianceCheck::ApproveTransferFrom)
+                                {
+                                    info!("<TRANSFER ERROR> {:?} fails {:?}", test_token, failed);
+                                    self.honeypot.insert(test_token, true);
+                                    continue;
+                                }
+                                Some(report)
+                            }
+                            Err(e) => {
+                                info!("Compliance checks of {:?} failed: {:?}", test_token, e);
+                                None
+                            }
+                        };
+
+                        // Transfers between wallets, the swaps only cover the pair's side
+                        let transfer_analysis = match self
+                            .simulator
+                            .analyze_transfer(test_token, *TRANSFER_RECIPIENT, amount)
+                            .await
+                        {
+                            Ok(analysis) => analysis,
+                            Err(e) => {
+                                info!("<TRANSFER ERROR> {:?} {:?}", test_token, e);
+                                self.honeypot.insert(test_token, true);
+                                continue;
+                            }
+                        };
+                        let transfer_tax = transfer_analysis.tax_bps as f64 / 10000.0;
+                        if transfer_tax >= self.config.thresholds.max_transfer_tax {
+                            info!(
+                                "⚠️ [{}] {:?} taxes transfers {:.2}%",
+                                idx,
+                                test_token,
+                                transfer_tax * 100.0
+                            );
+                            self.honeypot.insert(test_token, true);
+                            continue;
+                        }
+
+                        // The strategy has the last word: its limits (age included) and max_score
+                        let check = TokenCheck {
+                            token: test_token,
+                            pool: pool.address,
+                            is_proxy: is_proxy_contr,
+                            owner: self.simulator.check_owner(test_token).ok(),
+                            swappable: true,
+                            buy_tax: buy_tax_rate,
+                            sell_tax: sell_tax_rate,
+                            transferable: true,
+                            transfer_tax,
+                            transfer_analysis: Some(transfer_analysis),
+                            router_swap: None,
+                            compliance,
+                            anomalies: self.anomalies.get(&test_token).cloned().unwrap_or_default(),
+                            risk: None,
+                        };
+                        let signals = self.risk_signals(&check).await;
+                        let risk = RiskScore::new(&signals, &self.config.thresholds);
+                        if risk.is_honeypot {
+                            info!("⚠️ [{}] {:?} scores {}/100", idx, test_token, risk.score);
+                            self.honeypot.insert(test_token, true);
+                            continue;
+                        }
+
+                        match get_token_info(
+                            self.simulator.provider.clone(),
+                            test_token,
+                            Some(self.chain.multicall),
+                        )
+                        .await
+                        {
                             Ok(info) => {
                                 info!(
                                     "Added safe token info ({}). Total: {:?} tokens",
@@ -373,6 +756,128 @@ impl<M: Middleware + 'static> HoneypotFilter<M> {
         Ok(())
     }
 
+    // Run every single-token check against a WETH pool of the token
+    pub async fn check_token(&mut self, token: H160, pool: H160) -> Result<TokenCheck> {
+        let is_proxy = self.is_proxy(token).await;
+        let owner = self.simulator.check_owner(token).ok();
+        let (swappable, buy_tax, sell_tax) = self.simulate_swap(token, pool).await?;
+        let (transferable, simple_transfer_tax) = self.simulate_transfer(token).await?;
+        let transfer_analysis = match self.analyze_transfer(token).await {
+            Ok(analysis) => Some(analysis),
+            Err(e) => {
+                info!("Transfer analysis of {:?} failed: {:?}", token, e);
+                None
+            }
+        };
+        // The analysis follows the balances of both parties, prefer it over the simulator contract
+        let transfer_tax = transfer_analysis
+            .as_ref()
+            .map(|analysis| analysis.tax_bps as f64 / 10000.0)
+            .unwrap_or(simple_transfer_tax);
+        let compliance = match self.check_compliance(token).await {
+            Ok(report) => Some(report),
+            Err(e) => {
+                info!("Compliance checks of {:?} failed: {:?}", token, e);
+                None
+            }
+        };
+        let router_swap = if self.config.check_router {
+            match self.simulate_router_swap(token, pool).await {
+                Ok(check) => Some(check),
+                Err(e) => {
+                    info!("Router swap of {:?} failed: {:?}", token, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut check = TokenCheck {
+            token,
+            pool,
+            is_proxy,
+            owner,
+            swappable,
+            buy_tax,
+            sell_tax,
+            transferable,
+            transfer_tax,
+            transfer_analysis,
+            router_swap,
+            compliance,
+            anomalies: self.anomalies.get(&token).cloned().unwrap_or_default(),
+            risk: None,
+        };
+        let signals = self.risk_signals(&check).await;
+        check.risk = Some(RiskScore::new(&signals, &self.config.thresholds));
+
+        Ok(check)
+    }
+
+    // Gathers the static signals of the risk score next to the simulated ones of the check
+    // Signals that cannot be fetched (no V2 pool, no archive node) are left unknown
+    pub async fn risk_signals(&mut self, check: &TokenCheck) -> RiskSignals {
+        let token = check.token;
+        let provider = self.simulator.provider.clone();
+        let block_number = self.simulator.block_number;
+
+        let funded_admins = self.simulator.check_possible_admins(token).await.unwrap_or_default();
+
+        let mut code = self.simulator.get_code(token).unwrap_or_default().to_vec();
+        if check.is_proxy {
+            if let Ok(Some(implementation)) =
+                get_implementation(provider.clone(), token, block_number).await
+            {
+                code.extend(self.simulator.get_code(implementation).unwrap_or_default().to_vec());
+            }
+        }
+        let risky_selectors = find_selectors(&code, RISKY_SIGNATURES);
+
+        let liquidity = self.pool_liquidity(check.pool, token).ok();
+
+        let age = match get_deployment_block(provider, token, block_number).await {
+            Ok(deployed) => {
+                let blocks = (block_number - deployed).as_u32();
+                Some(self.chain.block_time * blocks)
+            }
+            Err(e) => {
+                info!("Deployment block of {:?} not found: {:?}", token, e);
+                None
+            }
+        };
+
+        RiskSignals {
+            swappable: check.swappable,
+            buy_tax: check.buy_tax,
+            sell_tax: check.sell_tax,
+            transfer_tax: check.transfer_tax,
+            is_proxy: check.is_proxy,
+            owner: check.owner,
+            funded_admins,
+            risky_selectors,
+            liquidity,
+            age,
+        }
+    }
+
+    // What the other side of `token` in a V2 pool holds, WETH or one of the stablecoins
+    pub fn pool_liquidity(&mut self, pool: H160, token: H160) -> Result<Liquidity> {
+        let (token0, token1) = self.simulator.v2_pool_tokens(pool)?;
+        let (reserve0, reserve1, _) = self.simulator.v2_pool_get_reserves(pool)?;
+        let (safe_token, reserve) =
+            if token0 == token { (token1, reserve1) } else { (token0, reserve0) };
+
+        let whole = |decimals: u8| reserve as f64 / 10f64.powi(decimals as i32);
+        if safe_token == self.chain.wrapped_native.address {
+            return Ok(Liquidity::Native(whole(self.chain.wrapped_native.decimals)));
+        }
+        match self.chain.stablecoins.iter().find(|stable| stable.address == safe_token) {
+            Some(stable) => Ok(Liquidity::Stable(whole(stable.decimals))),
+            None => Err(anyhow!("{pool:?} does not pair {token:?} with a safe token")),
+        }
+    }
+
     pub fn get_tax_rate(&self, token: H160) -> (f64, f64) {
         let buy_tax_rate = self.buy_tax.get(&token).unwrap_or(&0.0);
         let sell_tax_rate = self.sell_tax.get(&token).unwrap_or(&0.0);
@@ -0,0 +1,68 @@
+use ethers::types::transaction::eip2930::{AccessList, AccessListItem};
+use foundry_common::types::ToEthers;
+use foundry_evm::revm::{
+    interpreter::{opcode, Interpreter},
+    primitives::{Address, B256},
+    Database, EVMData, Inspector,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Collects the accounts and storage keys a transaction touches, like eth_createAccessList
+#[derive(Debug, Clone, Default)]
+pub struct AccessListInspector {
+    accessed: BTreeMap<Address, BTreeSet<B256>>,
+}
+
+fn is_precompile(address: &Address) -> bool {
+    let bytes = address.as_slice();
+    bytes[..19].iter().all(|byte| *byte == 0) && (1..=10).contains(&bytes[19])
+}
+
+impl AccessListInspector {
+    // The sender, the recipient and precompiles are warm anyway, so they are left out
+    pub fn access_list(&self, excluded: &[Address]) -> AccessList {
+        let items = self
+            .accessed
+            .iter()
+            .filter(|(address, _)| !excluded.contains(address) && !is_precompile(address))
+            .map(|(address, slots)| AccessListItem {
+                address: address.to_ethers(),
+                storage_keys: slots.iter().map(|slot| slot.to_ethers()).collect(),
+            })
+            .collect();
+        AccessList(items)
+    }
+
+    fn touch(&mut self, address: Address) {
+        self.accessed.entry(address).or_default();
+    }
+}
+
+impl<DB: Database> Inspector<DB> for AccessListInspector {
+    fn step(&mut self, interp: &mut Interpreter<'_>, _data: &mut EVMData<'_, DB>) {
+        let stack = interp.stack();
+        match interp.current_opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                if let Ok(slot) = stack.peek(0) {
+                    let address = interp.contract().address;
+                    self.accessed.entry(address).or_default().insert(B256::from(slot));
+                }
+            }
+            opcode::BALANCE
+            | opcode::EXTCODESIZE
+            | opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::SELFDESTRUCT => {
+                if let Ok(address) = stack.peek(0) {
+                    self.touch(Address::from_word(B256::from(address)));
+                }
+            }
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL => {
+                if let Ok(address) = stack.peek(1) {
+                    self.touch(Address::from_word(B256::from(address)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use ethers::abi::{decode, parse_abi, Function, ParamType, Token};
+use ethers::types::{Bytes, H160, U256};
+use foundry_common::types::ToEthers;
+use foundry_evm::revm::{
+    interpreter::{CallInputs, CallScheme, CreateInputs, Gas, InstructionResult},
+    primitives::{Address, Bytes as rBytes, CreateScheme},
+    Database, EVMData, Inspector,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::interfaces::simulator::SimulatorABI;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    pub from: H160,
+    // Unknown for creations until they return
+    pub to: Option<H160>,
+    pub value: U256,
+    pub input: Bytes,
+    pub output: Bytes,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub success: bool,
+    pub revert_reason: Option<String>,
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    // The innermost failing frame, which is usually where a honeypot token bails out
+    pub fn first_revert(&self) -> Option<&CallFrame> {
+        if self.success {
+            return None;
+        }
+        self.calls.iter().find_map(|call| call.first_revert()).or(Some(self))
+    }
+}
+
+// Builds the call tree of a transaction, the local counterpart of geth's callTracer
+#[derive(Debug, Clone, Default)]
+pub struct CallTracer {
+    stack: Vec<CallFrame>,
+    pub root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    fn enter(&mut self, frame: CallFrame) {
+        self.stack.push(frame);
+    }
+
+    fn exit(&mut self, to: Option<Address>, result: InstructionResult, gas: &Gas, output: &rBytes) {
+        let Some(mut frame) = self.stack.pop() else {
+            return;
+        };
+        if let Some(to) = to {
+            frame.to = Some(to.to_ethers());
+        }
+        frame.output = output.to_vec().into();
+        frame.gas_used = frame.gas_limit.saturating_sub(gas.remaining());
+        frame.success = result.is_ok();
+        if !frame.success {
+            frame.revert_reason = Some(decode_revert_reason(output, result));
+        }
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallTracer {
+    fn call(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, rBytes) {
+        let kind = match inputs.context.scheme {
+            CallScheme::Call => CallKind::Call,
+            CallScheme::CallCode => CallKind::CallCode,
+            CallScheme::DelegateCall => CallKind::DelegateCall,
+            CallScheme::StaticCall => CallKind::StaticCall,
+        };
+        self.enter(CallFrame {
+            kind,
+            from: inputs.context.caller.to_ethers(),
+            to: Some(inputs.contract.to_ethers()),
+            value: inputs.context.apparent_value.to_ethers(),
+            input: inputs.input.to_vec().into(),
+            output: Bytes::default(),
+            gas_limit: inputs.gas_limit,
+            gas_used: 0,
+            success: false,
+            revert_reason: None,
+            calls: vec![],
+        });
+
+        (InstructionResult::Continue, Gas::new(0), rBytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: rBytes,
+    ) -> (InstructionResult, Gas, rBytes) {
+        self.exit(None, ret, &remaining_gas, &out);
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<Address>, Gas, rBytes) {
+        let kind = match inputs.scheme {
+            CreateScheme::Create => CallKind::Create,
+            CreateScheme::Create2 { .. } => CallKind::Create2,
+        };
+        self.enter(CallFrame {
+            kind,
+            from: inputs.caller.to_ethers(),
+            to: None,
+            value: inputs.value.to_ethers(),
+            input: inputs.init_code.to_vec().into(),
+            output: Bytes::default(),
+            gas_limit: inputs.gas_limit,
+            gas_used: 0,
+            success: false,
+            revert_reason: None,
+            calls: vec![],
+        });
+
+        (InstructionResult::Continue, None, Gas::new(0), rBytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<Address>,
+        remaining_gas: Gas,
+        out: rBytes,
+    ) -> (InstructionResult, Option<Address>, Gas, rBytes) {
+        self.exit(address, ret, &remaining_gas, &out);
+        (ret, address, remaining_gas, out)
+    }
+}
+
+pub fn decode_revert_reason(output: &[u8], result: InstructionResult) -> String {
+    const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+    const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+    if output.len() >= 4 && output[..4] == ERROR_SELECTOR {
+        if let Ok(tokens) = decode(&[ParamType::String], &output[4..]) {
+            if let Some(Token::String(reason)) = tokens.into_iter().next() {
+                return reason;
+            }
+        }
+    }
+    if output.len() >= 4 && output[..4] == PANIC_SELECTOR {
+        if let Ok(tokens) = decode(&[ParamType::Uint(256)], &output[4..]) {
+            if let Some(Token::Uint(code)) = tokens.into_iter().next() {
+                return format!("Panic(0x{code:x})");
+            }
+        }
+    }
+    if output.is_empty() {
+        format!("{result:?}")
+    } else {
+        format!("0x{}", hex::encode(output))
+    }
+}
+
+// Selectors of the ERC-20, UniswapV2 pair/router and Simulator functions used to label frames
+pub struct KnownSelectors {
+    functions: HashMap<[u8; 4], Function>,
+}
+
+impl KnownSelectors {
+    pub fn new() -> Self {
+        let known = parse_abi(&[
+            "function totalSupply() external view returns (uint256)",
+            "function balanceOf(address) external view returns (uint256)",
+            "function allowance(address,address) external view returns (uint256)",
+            "function transfer(address,uint256) external returns (bool)",
+            "function transferFrom(address,address,uint256) external returns (bool)",
+            "function approve(address,uint256) external returns (bool)",
+            "function decimals() external view returns (uint8)",
+            "function symbol() external view returns (string)",
+            "function name() external view returns (string)",
+            "function owner() external view returns (address)",
+            "function getReserves() external view returns (uint112,uint112,uint32)",
+            "function token0() external view returns (address)",
+            "function token1() external view returns (address)",
+            "function swap(uint256,uint256,address,bytes) external",
+            "function sync() external",
+            "function skim(address) external",
+            "function mint(address) external returns (uint256)",
+            "function burn(address) external returns (uint256,uint256)",
+            "function deposit() external payable",
+            "function withdraw(uint256) external",
+        ])
+        .unwrap();
+
+        let simulator = SimulatorABI::new();
+        let functions = known
+            .functions()
+            .chain(simulator.abi.abi().functions())
+            .map(|function| (function.short_signature(), function.clone()))
+            .collect();
+
+        Self { functions }
+    }
+
+    pub fn describe(&self, input: &[u8]) -> String {
+        if input.len() < 4 {
+            return if input.is_empty() { "fallback()".to_string() } else { hex::encode(input) };
+        }
+
+        let selector: [u8; 4] = input[..4].try_into().unwrap();
+        match self.functions.get(&selector) {
+            Some(function) => match function.decode_input(&input[4..]) {
+                Ok(tokens) => {
+                    let args: Vec<String> = tokens.iter().map(|token| token.to_string()).collect();
+                    format!("{}({})", function.name, args.join(", "))
+                }
+                Err(_) => format!("{}(<invalid input>)", function.name),
+            },
+            None => format!("0x{}", hex::encode(input)),
+        }
+    }
+}
+
+impl Default for KnownSelectors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Renders the tree one frame per line with the gas used, e.g.
+// [23412] Call 0x001a…f187 -> 0xc02a…6cc2::transfer(4e17…a32c, 1000)
+pub fn format_call_tree(frame: &CallFrame) -> String {
+    let selectors = KnownSelectors::new();
+    let mut out = String::new();
+    write_frame(&selectors, frame, 0, &mut out);
+    out
+}
+
+fn write_frame(selectors: &KnownSelectors, frame: &CallFrame, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let target = match frame.to {
+        Some(to) => format!("{to:?}"),
+        None => "<create>".to_string(),
+    };
+    let call = match frame.kind {
+        CallKind::Create | CallKind::Create2 => format!("{} bytes of init code", frame.input.len()),
+        _ => selectors.describe(&frame.input),
+    };
+    let value =
+        if frame.value.is_zero() { String::new() } else { format!(" value: {}", frame.value) };
+
+    out.push_str(&format!(
+        "{indent}[{}] {:?} {:?} -> {target}::{call}{value}\n",
+        frame.gas_used, frame.kind, frame.from
+    ));
+    for call in &frame.calls {
+        write_frame(selectors, call, depth + 1, out);
+    }
+
+    match &frame.revert_reason {
+        Some(reason) => out.push_str(&format!("{indent}  <- REVERT: {reason}\n")),
+        None if !frame.output.is_empty() => {
+            out.push_str(&format!("{indent}  <- 0x{}\n", hex::encode(&frame.output)))
+        }
+        None => {}
+    }
+}
@@ -0,0 +1,3 @@
+pub mod access_list;
+pub mod call_tracer;
+pub mod storage;
@@ -0,0 +1,23 @@
+use foundry_evm::revm::{
+    interpreter::{opcode, Interpreter},
+    primitives::{Address, U256 as rU256},
+    Database, EVMData, Inspector,
+};
+
+// Records every SLOAD in execution order, keyed by the account whose storage is read
+// (the proxy for delegatecalls), the way forge's stdstore finds the slot behind a getter
+#[derive(Debug, Clone, Default)]
+pub struct StorageRecorder {
+    pub reads: Vec<(Address, rU256)>,
+}
+
+impl<DB: Database> Inspector<DB> for StorageRecorder {
+    fn step(&mut self, interp: &mut Interpreter<'_>, _data: &mut EVMData<'_, DB>) {
+        if interp.current_opcode() != opcode::SLOAD {
+            return;
+        }
+        if let Ok(slot) = interp.stack().peek(0) {
+            self.reads.push((interp.contract().address, slot));
+        }
+    }
+}
@@ -1,4 +1,6 @@
+pub mod factory;
 pub mod ownable;
 pub mod pool;
+pub mod router;
 pub mod simulator;
 pub mod token;
@@ -2,7 +2,7 @@ use anyhow::Result;
 use bytes::Bytes as OutputBytes;
 use ethers::abi::parse_abi;
 use ethers::prelude::BaseContract;
-use ethers::types::Bytes;
+use ethers::types::{Bytes, H160};
 
 #[derive(Clone)]
 pub struct V2PoolABI {
@@ -12,8 +12,13 @@ pub struct V2PoolABI {
 impl V2PoolABI {
     pub fn new() -> Self {
         let abi = BaseContract::from(
-            parse_abi(&["function getReserves() external view returns (uint112,uint112,uint32)"])
-                .unwrap(),
+            parse_abi(&[
+                "function getReserves() external view returns (uint112,uint112,uint32)",
+                "function token0() external view returns (address)",
+                "function token1() external view returns (address)",
+                "function factory() external view returns (address)",
+            ])
+            .unwrap(),
         );
         Self { abi }
     }
@@ -27,4 +32,34 @@ impl V2PoolABI {
         let out = self.abi.decode_output("getReserves", output)?;
         Ok(out)
     }
+
+    pub fn token0_input(&self) -> Result<Bytes> {
+        let calldata = self.abi.encode("token0", ())?;
+        Ok(calldata)
+    }
+
+    pub fn token0_output(&self, output: OutputBytes) -> Result<H160> {
+        let out = self.abi.decode_output("token0", output)?;
+        Ok(out)
+    }
+
+    pub fn token1_input(&self) -> Result<Bytes> {
+        let calldata = self.abi.encode("token1", ())?;
+        Ok(calldata)
+    }
+
+    pub fn token1_output(&self, output: OutputBytes) -> Result<H160> {
+        let out = self.abi.decode_output("token1", output)?;
+        Ok(out)
+    }
+
+    pub fn factory_input(&self) -> Result<Bytes> {
+        let calldata = self.abi.encode("factory", ())?;
+        Ok(calldata)
+    }
+
+    pub fn factory_output(&self, output: OutputBytes) -> Result<H160> {
+        let out = self.abi.decode_output("factory", output)?;
+        Ok(out)
+    }
 }
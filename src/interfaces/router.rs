@@ -0,0 +1,74 @@
+use anyhow::Result;
+use bytes::Bytes as OutputBytes;
+use ethers::abi::parse_abi;
+use ethers::prelude::BaseContract;
+use ethers::types::{Bytes, H160, U256};
+
+#[derive(Clone)]
+pub struct V2RouterABI {
+    pub abi: BaseContract,
+}
+
+impl V2RouterABI {
+    pub fn new() -> Self {
+        let abi = BaseContract::from(
+            parse_abi(&[
+                "function factory() external pure returns (address)",
+                "function getAmountsOut(uint256,address[]) external view returns (uint256[])",
+                "function swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256) external payable",
+                "function swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256) external",
+            ])
+            .unwrap(),
+        );
+        Self { abi }
+    }
+
+    pub fn factory_input(&self) -> Result<Bytes> {
+        let calldata = self.abi.encode("factory", ())?;
+        Ok(calldata)
+    }
+
+    pub fn factory_output(&self, output: OutputBytes) -> Result<H160> {
+        let out = self.abi.decode_output("factory", output)?;
+        Ok(out)
+    }
+
+    pub fn get_amounts_out_input(&self, amount_in: U256, path: Vec<H160>) -> Result<Bytes> {
+        let calldata = self.abi.encode("getAmountsOut", (amount_in, path))?;
+        Ok(calldata)
+    }
+
+    pub fn get_amounts_out_output(&self, output: OutputBytes) -> Result<Vec<U256>> {
+        let out = self.abi.decode_output("getAmountsOut", output)?;
+        Ok(out)
+    }
+
+    pub fn swap_exact_eth_for_tokens_input(
+        &self,
+        amount_out_min: U256,
+        path: Vec<H160>,
+        to: H160,
+        deadline: U256,
+    ) -> Result<Bytes> {
+        let calldata = self.abi.encode(
+            "swapExactETHForTokensSupportingFeeOnTransferTokens",
+            (amount_out_min, path, to, deadline),
+        )?;
+        Ok(calldata)
+    }
+
+    pub fn swap_exact_tokens_for_eth_input(
+        &self,
+        amount_in: U256,
+        amount_out_min: U256,
+        path: Vec<H160>,
+        to: H160,
+        deadline: U256,
+    ) -> Result<Bytes> {
+        let calldata = self.abi.encode(
+            "swapExactTokensForETHSupportingFeeOnTransferTokens",
+            (amount_in, amount_out_min, path, to, deadline),
+        )?;
+        Ok(calldata)
+    }
+}
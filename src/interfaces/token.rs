@@ -14,8 +14,12 @@ impl TokenABI {
         let abi = BaseContract::from(
             parse_abi(&[
                 "function balanceOf(address) external view returns (uint256)",
-                "function approve(address spender, uint256 value) external view returns (bool)",
+                "function approve(address spender, uint256 value) external returns (bool)",
                 "function transfer(address,uint256) external returns (bool)",
+                "function totalSupply() external view returns (uint256)",
+                "function allowance(address,address) external view returns (uint256)",
+                "function transferFrom(address,address,uint256) external returns (bool)",
+                "function decimals() external view returns (uint8)",
             ])
             .unwrap(),
         );
@@ -32,14 +36,13 @@ impl TokenABI {
         Ok(out)
     }
 
-    pub fn approve_input(&self, spender: H160) -> Result<Bytes> {
-        let calldata = self.abi.encode("approve", (spender, U256::MAX))?;
+    pub fn approve_input(&self, spender: H160, amount: U256) -> Result<Bytes> {
+        let calldata = self.abi.encode("approve", (spender, amount))?;
         Ok(calldata)
     }
 
     pub fn approve_output(&self, output: OutputBytes) -> Result<bool> {
-        let out = self.abi.decode_output("approve", output)?;
-        Ok(out)
+        self.success_output("approve", output)
     }
 
     pub fn transfer_input(&self, recipient: H160, amount: U256) -> Result<Bytes> {
@@ -48,7 +51,60 @@ impl TokenABI {
     }
 
     pub fn transfer_output(&self, output: OutputBytes) -> Result<bool> {
-        let out = self.abi.decode("transfer", output)?;
+        self.success_output("transfer", output)
+    }
+
+    pub fn total_supply_input(&self) -> Result<Bytes> {
+        let calldata = self.abi.encode("totalSupply", ())?;
+        Ok(calldata)
+    }
+
+    pub fn total_supply_output(&self, output: OutputBytes) -> Result<U256> {
+        let out = self.abi.decode_output("totalSupply", output)?;
+        Ok(out)
+    }
+
+    pub fn decimals_input(&self) -> Result<Bytes> {
+        let calldata = self.abi.encode("decimals", ())?;
+        Ok(calldata)
+    }
+
+    pub fn decimals_output(&self, output: OutputBytes) -> Result<u8> {
+        let out = self.abi.decode_output("decimals", output)?;
+        Ok(out)
+    }
+
+    pub fn allowance_input(&self, owner: H160, spender: H160) -> Result<Bytes> {
+        let calldata = self.abi.encode("allowance", (owner, spender))?;
+        Ok(calldata)
+    }
+
+    pub fn allowance_output(&self, output: OutputBytes) -> Result<U256> {
+        let out = self.abi.decode_output("allowance", output)?;
+        Ok(out)
+    }
+
+    pub fn transfer_from_input(
+        &self,
+        sender: H160,
+        recipient: H160,
+        amount: U256,
+    ) -> Result<Bytes> {
+        let calldata = self.abi.encode("transferFrom", (sender, recipient, amount))?;
+        Ok(calldata)
+    }
+
+    pub fn transfer_from_output(&self, output: OutputBytes) -> Result<bool> {
+        self.success_output("transferFrom", output)
+    }
+
+    // Same rule as SafeERC20: tokens like USDT return nothing, which counts as success,
+    // anything else has to decode to a bool
+    pub fn success_output(&self, function: &str, output: OutputBytes) -> Result<bool> {
+        if output.is_empty() {
+            return Ok(true);
+        }
+        let out = self.abi.decode_output(function, output)?;
         Ok(out)
     }
 }
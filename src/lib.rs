@@ -1,9 +1,21 @@
+pub mod cli;
+pub mod compliance;
+pub mod config;
 pub mod constants;
+pub mod fixtures;
 pub mod honeypot;
+pub mod inspectors;
 pub mod interfaces;
+pub mod mempool;
 pub mod paths;
 pub mod pools;
+pub mod provider;
+pub mod replay;
+pub mod risk;
+pub mod server;
 pub mod simulator;
+pub mod tax;
 pub mod tokens;
 pub mod trace;
+pub mod transfer;
 pub mod utils;
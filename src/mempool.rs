@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Result};
+use ethers::prelude::Lazy;
+use ethers::types::{Transaction, H160, H256, U256, U64};
+use ethers::utils::keccak256;
+use ethers_providers::{Middleware, PubsubClient};
+use foundry_common::types::ToEthers;
+use foundry_evm::revm::primitives::{ExecutionResult, Log as rLog};
+use futures::stream::{BoxStream, StreamExt};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::{collections::BTreeSet, sync::Arc};
+use tokio::sync::broadcast::Sender;
+
+use crate::constants::TRANSFER_TOPIC;
+use crate::simulator::{EvmSimulator, RollMode};
+
+pub static V2_SYNC_TOPIC: Lazy<H256> = Lazy::new(|| H256::from(keccak256("Sync(uint112,uint112)")));
+pub static V2_SWAP_TOPIC: Lazy<H256> =
+    Lazy::new(|| H256::from(keccak256("Swap(address,uint256,uint256,uint256,uint256,address)")));
+pub static V3_SWAP_TOPIC: Lazy<H256> = Lazy::new(|| {
+    H256::from(keccak256("Swap(address,address,int256,int256,uint160,uint128,int24)"))
+});
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenTransfer {
+    pub token: H160,
+    pub from: H160,
+    pub to: H160,
+    pub amount: U256,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReserveChange {
+    pub pool: H160,
+    pub reserve0: U256,
+    pub reserve1: U256,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingTxEffects {
+    pub tx: Transaction,
+    // the head block the transaction was simulated on top of
+    pub block_number: U64,
+    pub success: bool,
+    pub gas_used: u64,
+    pub touched_pools: Vec<H160>,
+    pub transfers: Vec<TokenTransfer>,
+    pub reserves: Vec<ReserveChange>,
+}
+
+fn word(data: &[u8], idx: usize) -> Option<U256> {
+    data.get(idx * 32..(idx + 1) * 32).map(U256::from_big_endian)
+}
+
+fn topic_address(topic: &H256) -> H160 {
+    H160::from_slice(&topic.as_bytes()[12..])
+}
+
+// Picks the ERC-20 transfers, V2 reserve updates and swapped pools out of the simulated logs
+pub fn decode_effects(logs: &[rLog]) -> (Vec<H160>, Vec<TokenTransfer>, Vec<ReserveChange>) {
+    let mut touched_pools = BTreeSet::new();
+    let mut transfers = Vec::new();
+    let mut reserves = Vec::new();
+
+    for log in logs {
+        let address = log.address.to_ethers();
+        let topics: Vec<H256> = log.topics.iter().map(|topic| topic.to_ethers()).collect();
+        let Some(topic0) = topics.first() else {
+            continue;
+        };
+
+        // ERC-721 transfers index the token id as a fourth topic, skip those
+        if *topic0 == *TRANSFER_TOPIC && topics.len() == 3 {
+            if let Some(amount) = word(&log.data, 0) {
+                transfers.push(TokenTransfer {
+                    token: address,
+                    from: topic_address(&topics[1]),
+                    to: topic_address(&topics[2]),
+                    amount,
+                });
+            }
+        } else if *topic0 == *V2_SYNC_TOPIC {
+            if let (Some(reserve0), Some(reserve1)) = (word(&log.data, 0), word(&log.data, 1)) {
+                touched_pools.insert(address);
+                reserves.push(ReserveChange { pool: address, reserve0, reserve1 });
+            }
+        } else if *topic0 == *V2_SWAP_TOPIC || *topic0 == *V3_SWAP_TOPIC {
+            touched_pools.insert(address);
+        }
+    }
+
+    (touched_pools.into_iter().collect(), transfers, reserves)
+}
+
+pub struct MempoolWatcher<M> {
+    pub provider: Arc<M>,
+    pub owner: H160,
+    // use the full transaction subscription instead of hashes + eth_getTransactionByHash
+    pub full_txs: bool,
+    simulator: Option<EvmSimulator<M>>,
+}
+
+impl<M> MempoolWatcher<M>
+where
+    M: Middleware + 'static,
+    M::Provider: PubsubClient,
+{
+    pub fn new(provider: Arc<M>, owner: H160, full_txs: bool) -> Self {
+        Self { provider, owner, full_txs, simulator: None }
+    }
+
+    async fn fork_at(&mut self, block_number: U64) -> Result<()> {
+        // Keep the warm caches and only drop what changed since the last head
+        if let Some(simulator) = self.simulator.as_mut() {
+            return simulator.roll_to(block_number, RollMode::Touched).await;
+        }
+
+        let mut simulator = EvmSimulator::new(self.provider.clone(), self.owner, block_number);
+        simulator.load_block_env().await?;
+        self.simulator = Some(simulator);
+        Ok(())
+    }
+
+    pub fn simulate(&mut self, tx: Transaction) -> Result<PendingTxEffects> {
+        // Nothing is committed, so every transaction runs on the head state
+        let simulator = self.simulator.as_mut().ok_or(anyhow!("Simulator is not forked yet"))?;
+        let result = simulator.replay_tx(&tx, false)?;
+
+        let (success, logs) = match &result {
+            ExecutionResult::Success { logs, .. } => (true, logs.as_slice()),
+            _ => (false, &[][..]),
+        };
+        let (touched_pools, transfers, reserves) = decode_effects(logs);
+
+        Ok(PendingTxEffects {
+            tx,
+            block_number: simulator.block_number,
+            success,
+            gas_used: result.gas_used(),
+            touched_pools,
+            transfers,
+            reserves,
+        })
+    }
+
+    // Follows the head and simulates every pending transaction on top of it,
+    // the decoded effects are sent to every subscriber of the channel
+    pub async fn run(&mut self, sender: Sender<PendingTxEffects>) -> Result<()> {
+        let provider = self.provider.clone();
+
+        let head = provider.get_block_number().await?;
+        self.fork_at(head).await?;
+
+        let mut blocks = provider.subscribe_blocks().await?;
+        let mut txs: BoxStream<'_, Transaction> = if self.full_txs {
+            provider.subscribe_full_pending_txs().await?.boxed()
+        } else {
+            let client = provider.clone();
+            provider
+                .subscribe_pending_txs()
+                .await?
+                .filter_map(move |hash| {
+                    let client = client.clone();
+                    async move { client.get_transaction(hash).await.ok().flatten() }
+                })
+                .boxed()
+        };
+
+        info!("Watching the mempool from block {}", head);
+        loop {
+            tokio::select! {
+                block = blocks.next() => match block.and_then(|block| block.number) {
+                    Some(block_number) => {
+                        if let Err(e) = self.fork_at(block_number).await {
+                            warn!("Failed to fork at block {}: {:?}", block_number, e);
+                        }
+                    }
+                    None => break,
+                },
+                tx = txs.next() => match tx {
+                    // mined in the meantime
+                    Some(tx) if tx.block_number.is_some() => continue,
+                    Some(tx) => match self.simulate(tx) {
+                        Ok(effects) => {
+                            // no subscribers left is not an error, keep watching
+                            let _ = sender.send(effects);
+                        }
+                        Err(e) => debug!("Pending tx simulation failed: {:?}", e),
+                    },
+                    None => break,
+                },
+            }
+        }
+
+        Err(anyhow!("Mempool subscription closed"))
+    }
+}
@@ -3,20 +3,17 @@ This module is adapted from the mev-templates code:
 https://github.com/solidquant/mev-templates
 */
 use anyhow::{Ok, Result};
-use cfmms::{
-    dex::{Dex, DexVariant as CfmmsDexVariant},
-    pool::Pool as CfmmsPool,
-    sync::sync_pairs,
-};
+use cfmms::{dex::Dex, pool::Pool as CfmmsPool, sync::sync_pairs};
 use csv::StringRecord;
-use ethers::{
-    providers::{Provider, Ws},
-    types::H160,
-};
+use ethers::{providers::Middleware, types::H160};
 use log::info;
-use std::{collections::HashMap, path::Path, str::FromStr, sync::Arc};
+use serde::Deserialize;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
-#[derive(Debug, Clone)]
+use crate::config::FactoryRegistry;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DexVariant {
     UniswapV2,
     UniswapV3,
@@ -73,12 +70,12 @@ impl Pool {
     }
 }
 
-pub async fn load_all_pools(
-    wss_url: String,
-    factories: Vec<(&str, CfmmsDexVariant, u64)>,
+pub async fn load_all_pools<M: Middleware + 'static>(
+    provider: Arc<M>,
+    registry: &FactoryRegistry,
 ) -> Result<Vec<Pool>> {
     // Load from cached file if the file exists
-    let file_path = Path::new("src/.cached-pools.csv");
+    let file_path = registry.pool_cache.as_path();
     if file_path.exists() {
         let mut reader = csv::Reader::from_path(file_path)?;
 
@@ -91,13 +88,16 @@ pub async fn load_all_pools(
         return Ok(pools_vec);
     }
 
-    let ws = Ws::connect(wss_url).await?;
-    let provider = Arc::new(Provider::new(ws));
-
-    let dexes: Vec<_> = factories
-        .into_iter()
-        .map(|(address, variant, number)| {
-            Dex::new(H160::from_str(address).unwrap(), variant, number, Some(3000))
+    let dexes: Vec<_> = registry
+        .factories
+        .iter()
+        .map(|factory| {
+            Dex::new(
+                factory.address,
+                factory.cfmms_variant(),
+                factory.deployed_block,
+                Some(factory.fee),
+            )
         })
         .collect();
 
@@ -146,6 +146,10 @@ pub async fn load_all_pools(
     Ok(pools_vec)
 }
 
+pub fn find_pool(pools: &[Pool], token_a: H160, token_b: H160) -> Option<&Pool> {
+    pools.iter().find(|pool| pool.has_token(token_a) && pool.has_token(token_b))
+}
+
 pub fn get_tokens(pools: &Vec<Pool>) -> HashMap<H160, u8> {
     let mut tokens = HashMap::new();
     for pool in pools {
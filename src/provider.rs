@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, HttpClientError, HttpRateLimitRetryPolicy, Ipc, JsonRpcClient, Provider, ProviderError,
+    PubsubClient, RetryClient, RetryClientBuilder, RetryPolicy, Ws,
+};
+use ethers::types::U256;
+use futures::channel::mpsc::UnboundedReceiver;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::RawValue;
+use std::{fmt::Debug, time::Duration};
+use tokio::{sync::Mutex, time::Instant};
+use url::Url;
+
+const HTTP_TIMEOUT_RETRIES: u32 = 3;
+const HTTP_RATE_LIMIT_RETRIES: u32 = 10;
+const HTTP_INITIAL_BACKOFF_MS: u64 = 500;
+const WS_RECONNECTS: usize = 5;
+
+// JSON-RPC error codes of throttled requests, sent inside HTTP 200 responses (Alchemy, Infura)
+const RATE_LIMIT_CODES: &[i64] = &[429, -32005];
+// Throttling reported under a generic code, matched on the lowercased message
+const RATE_LIMIT_MESSAGES: &[&str] =
+    &["rate limit", "too many requests", "request limit", "compute units per second"];
+
+pub type RpcProvider = Provider<RpcClient>;
+
+#[derive(Debug)]
+pub enum Transport {
+    Http(RetryClient<Http>),
+    Ws(Ws),
+    Ipc(Ipc),
+}
+
+impl Transport {
+    // The transport is picked from the scheme of the url, anything else is treated as an IPC path
+    pub async fn connect(url: &str) -> Result<Self> {
+        let transport = match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("http") | Some("https") => {
+                let url = Url::parse(url).map_err(|e| anyhow!("Failed to parse RPC URL: {e:?}"))?;
+                let client = RetryClientBuilder::default()
+                    .timeout_retries(HTTP_TIMEOUT_RETRIES)
+                    .rate_limit_retries(HTTP_RATE_LIMIT_RETRIES)
+                    .initial_backoff(Duration::from_millis(HTTP_INITIAL_BACKOFF_MS))
+                    .build(Http::new(url), Box::new(RateLimitRetryPolicy));
+                Transport::Http(client)
+            }
+            Some("ws") | Some("wss") => {
+                Transport::Ws(Ws::connect_with_reconnects(url, WS_RECONNECTS).await?)
+            }
+            Some("ipc") | Some("file") => {
+                Transport::Ipc(Ipc::connect(url.split_once("://").unwrap().1).await?)
+            }
+            Some(scheme) => return Err(anyhow!("Unsupported RPC scheme: {scheme}")),
+            None => Transport::Ipc(Ipc::connect(url).await?),
+        };
+        Ok(transport)
+    }
+}
+
+// Retries HTTP 429s like HttpRateLimitRetryPolicy, and also the JSON-RPC errors providers
+// answer with when throttling, which arrive as successful HTTP responses
+#[derive(Debug, Default)]
+pub struct RateLimitRetryPolicy;
+
+impl RetryPolicy<HttpClientError> for RateLimitRetryPolicy {
+    fn should_retry(&self, error: &HttpClientError) -> bool {
+        if HttpRateLimitRetryPolicy.should_retry(error) {
+            return true;
+        }
+        match error {
+            HttpClientError::JsonRpcError(error) => {
+                let message = error.message.to_lowercase();
+                RATE_LIMIT_CODES.contains(&error.code)
+                    || RATE_LIMIT_MESSAGES.iter().any(|pattern| message.contains(pattern))
+            }
+            _ => false,
+        }
+    }
+
+    fn backoff_hint(&self, error: &HttpClientError) -> Option<Duration> {
+        HttpRateLimitRetryPolicy.backoff_hint(error)
+    }
+}
+
+// Spaces out outgoing requests so that at most `requests_per_second` are sent
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        let interval = Duration::from_secs(1) / requests_per_second.max(1);
+        Self { interval, next: Mutex::new(Instant::now()) }
+    }
+
+    pub async fn acquire(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep_until(*next).await;
+        }
+        *next = (*next).max(now) + self.interval;
+    }
+}
+
+#[derive(Debug)]
+pub struct RpcClient {
+    pub transport: Transport,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl RpcClient {
+    pub fn new(transport: Transport, rate_limit: Option<u32>) -> Self {
+        Self { transport, rate_limiter: rate_limit.map(RateLimiter::new) }
+    }
+
+    pub fn supports_subscriptions(&self) -> bool {
+        !matches!(self.transport, Transport::Http(_))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcClient {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        match &self.transport {
+            Transport::Http(client) => client.request(method, params).await.map_err(Into::into),
+            Transport::Ws(client) => client.request(method, params).await.map_err(Into::into),
+            Transport::Ipc(client) => client.request(method, params).await.map_err(Into::into),
+        }
+    }
+}
+
+impl PubsubClient for RpcClient {
+    type NotificationStream = UnboundedReceiver<Box<RawValue>>;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, Self::Error> {
+        match &self.transport {
+            Transport::Http(_) => Err(ProviderError::UnsupportedRPC),
+            Transport::Ws(client) => client.subscribe(id).map_err(Into::into),
+            Transport::Ipc(client) => client.subscribe(id).map_err(Into::into),
+        }
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), Self::Error> {
+        match &self.transport {
+            Transport::Http(_) => Err(ProviderError::UnsupportedRPC),
+            Transport::Ws(client) => client.unsubscribe(id).map_err(Into::into),
+            Transport::Ipc(client) => client.unsubscribe(id).map_err(Into::into),
+        }
+    }
+}
+
+// Connects to an HTTP, WS or IPC endpoint depending on the given url
+// HTTP requests are retried with backoff on rate limits (HTTP 429 or throttling JSON-RPC errors)
+// and timeouts, WS reconnects on drop
+pub async fn connect(url: &str, rate_limit: Option<u32>) -> Result<RpcProvider> {
+    let transport = Transport::connect(url).await?;
+    Ok(Provider::new(RpcClient::new(transport, rate_limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::JsonRpcError;
+
+    fn rpc_error(code: i64, message: &str) -> HttpClientError {
+        HttpClientError::JsonRpcError(JsonRpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        })
+    }
+
+    #[test]
+    fn retries_rate_limit_codes() {
+        assert!(RateLimitRetryPolicy.should_retry(&rpc_error(429, "")));
+        assert!(RateLimitRetryPolicy.should_retry(&rpc_error(-32005, "limit exceeded")));
+    }
+
+    #[test]
+    fn retries_rate_limit_messages() {
+        let policy = RateLimitRetryPolicy;
+        assert!(policy.should_retry(&rpc_error(-32000, "Too Many Requests")));
+        assert!(policy.should_retry(&rpc_error(-32016, "Your IP has exceeded its rate limit")));
+        assert!(policy.should_retry(&rpc_error(
+            -32000,
+            "Your app has exceeded its compute units per second capacity"
+        )));
+    }
+
+    #[test]
+    fn does_not_retry_execution_errors() {
+        let policy = RateLimitRetryPolicy;
+        assert!(!policy.should_retry(&rpc_error(3, "execution reverted")));
+        assert!(!policy.should_retry(&rpc_error(-32000, "nonce too low")));
+        assert!(!policy.should_retry(&rpc_error(-32602, "invalid argument 0")));
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Result};
+use ethers::types::{Bytes, Log, Transaction, H160, H256, U64};
+use ethers_providers::Middleware;
+use foundry_common::types::ToEthers;
+use foundry_evm::revm::primitives::{ExecutionResult, Log as rLog};
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::constants::DEFAULT_SENDER;
+use crate::simulator::EvmSimulator;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogSummary {
+    pub address: H160,
+    pub topics: Vec<H256>,
+    pub data: Bytes,
+}
+
+impl From<&Log> for LogSummary {
+    fn from(log: &Log) -> Self {
+        Self { address: log.address, topics: log.topics.clone(), data: log.data.clone() }
+    }
+}
+
+impl From<&rLog> for LogSummary {
+    fn from(log: &rLog) -> Self {
+        Self {
+            address: log.address.to_ethers(),
+            topics: log.topics.iter().map(|topic| topic.to_ethers()).collect(),
+            data: log.data.to_vec().into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionSummary {
+    pub success: bool,
+    pub gas_used: u64,
+    pub logs: Vec<LogSummary>,
+}
+
+impl From<&ExecutionResult> for ExecutionSummary {
+    fn from(result: &ExecutionResult) -> Self {
+        match result {
+            ExecutionResult::Success { gas_used, logs, .. } => Self {
+                success: true,
+                gas_used: *gas_used,
+                logs: logs.iter().map(LogSummary::from).collect(),
+            },
+            ExecutionResult::Revert { gas_used, .. } | ExecutionResult::Halt { gas_used, .. } => {
+                Self { success: false, gas_used: *gas_used, logs: vec![] }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub hash: H256,
+    pub block_number: U64,
+    pub transaction_index: u64,
+    // number of transactions of the same block replayed before the target
+    pub replayed_before: usize,
+    // preceding transactions the EVM could not execute, the target then ran on diverged state
+    pub diverged: Vec<(H256, String)>,
+    pub expected: ExecutionSummary,
+    pub actual: ExecutionSummary,
+    pub mismatches: Vec<String>,
+}
+
+impl ReplayReport {
+    pub fn matches(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+fn diff(expected: &ExecutionSummary, actual: &ExecutionSummary) -> Vec<String> {
+    let mut mismatches = Vec::new();
+
+    if expected.success != actual.success {
+        mismatches.push(format!("status: expected {}, got {}", expected.success, actual.success));
+    }
+    if expected.gas_used != actual.gas_used {
+        mismatches
+            .push(format!("gas used: expected {}, got {}", expected.gas_used, actual.gas_used));
+    }
+    if expected.logs.len() != actual.logs.len() {
+        mismatches.push(format!(
+            "log count: expected {}, got {}",
+            expected.logs.len(),
+            actual.logs.len()
+        ));
+    }
+    for (idx, (expected, actual)) in expected.logs.iter().zip(actual.logs.iter()).enumerate() {
+        if expected.address != actual.address {
+            mismatches.push(format!(
+                "log {idx} address: expected {:?}, got {:?}",
+                expected.address, actual.address
+            ));
+        }
+        if expected.topics != actual.topics {
+            mismatches.push(format!("log {idx} topics differ"));
+        }
+        if expected.data != actual.data {
+            mismatches.push(format!("log {idx} data differs"));
+        }
+    }
+
+    mismatches
+}
+
+// Forks at block - 1, replays every transaction of the block that came before the target,
+// executes the target and compares the outcome with its on-chain receipt
+pub async fn replay_transaction<M: Middleware + 'static>(
+    provider: Arc<M>,
+    hash: H256,
+) -> Result<ReplayReport> {
+    let tx =
+        provider.get_transaction(hash).await?.ok_or(anyhow!("Transaction {hash:?} not found"))?;
+    let block_number = tx.block_number.ok_or(anyhow!("Transaction {hash:?} is still pending"))?;
+    let transaction_index =
+        tx.transaction_index.ok_or(anyhow!("Transaction {hash:?} has no index"))?.as_u64();
+
+    let receipt = provider
+        .get_transaction_receipt(hash)
+        .await?
+        .ok_or(anyhow!("Receipt of {hash:?} not found"))?;
+    let block = provider
+        .get_block_with_txs(block_number)
+        .await?
+        .ok_or(anyhow!("Block {block_number} not found"))?;
+
+    let fork_block = block_number
+        .checked_sub(U64::one())
+        .ok_or(anyhow!("{hash:?} is in the genesis block, there is no block to fork"))?;
+    let mut simulator = EvmSimulator::new(provider.clone(), *DEFAULT_SENDER, fork_block);
+
+    // Execute in the environment and under the hardfork rules of the mined block
+    let chain_id = provider.get_chainid().await?;
+    simulator.evm.env.cfg.chain_id = chain_id.as_u64();
+    simulator.apply_header(&block);
+    simulator.apply_spec();
+
+    let preceding: Vec<&Transaction> = block
+        .transactions
+        .iter()
+        .filter(|block_tx| {
+            block_tx.transaction_index.map(|idx| idx.as_u64() < transaction_index).unwrap_or(false)
+        })
+        .collect();
+
+    info!("Replaying {} transactions before {:?}", preceding.len(), hash);
+    let mut diverged = Vec::new();
+    for block_tx in &preceding {
+        // Reverted transactions still change state (nonce, gas), EVM errors mean we diverged
+        if let Err(e) = simulator.replay_tx(block_tx, true) {
+            warn!("Failed to replay {:?}: {:?}", block_tx.hash, e);
+            diverged.push((block_tx.hash, e.to_string()));
+        }
+    }
+
+    let result = simulator.replay_tx(&tx, true)?;
+    let actual = ExecutionSummary::from(&result);
+    let expected = ExecutionSummary {
+        success: receipt.status.map(|status| status.as_u64() == 1).unwrap_or(false),
+        gas_used: receipt.gas_used.unwrap_or_default().as_u64(),
+        logs: receipt.logs.iter().map(LogSummary::from).collect(),
+    };
+    let mut mismatches = diff(&expected, &actual);
+    if !diverged.is_empty() {
+        mismatches.push(format!(
+            "state diverged: {} preceding transactions failed to replay",
+            diverged.len()
+        ));
+    }
+
+    Ok(ReplayReport {
+        hash,
+        block_number,
+        transaction_index,
+        replayed_before: preceding.len(),
+        diverged,
+        expected,
+        actual,
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(address: u64, topic: u64, data: &[u8]) -> LogSummary {
+        LogSummary {
+            address: H160::from_low_u64_be(address),
+            topics: vec![H256::from_low_u64_be(topic)],
+            data: data.to_vec().into(),
+        }
+    }
+
+    fn summary(success: bool, gas_used: u64, logs: Vec<LogSummary>) -> ExecutionSummary {
+        ExecutionSummary { success, gas_used, logs }
+    }
+
+    #[test]
+    fn identical_executions_match() {
+        let expected = summary(true, 21000, vec![log(1, 2, &[3])]);
+        assert!(diff(&expected, &expected.clone()).is_empty());
+    }
+
+    #[test]
+    fn status_and_gas_mismatches() {
+        let mismatches = diff(&summary(true, 50000, vec![]), &summary(false, 48000, vec![]));
+        assert_eq!(
+            mismatches,
+            vec!["status: expected true, got false", "gas used: expected 50000, got 48000"]
+        );
+    }
+
+    #[test]
+    fn log_mismatches() {
+        let expected = summary(true, 21000, vec![log(1, 2, &[3]), log(1, 2, &[3])]);
+        let actual = summary(true, 21000, vec![log(4, 2, &[3]), log(1, 5, &[6]), log(1, 2, &[3])]);
+        assert_eq!(
+            diff(&expected, &actual),
+            vec![
+                "log count: expected 2, got 3".to_string(),
+                format!(
+                    "log 0 address: expected {:?}, got {:?}",
+                    H160::from_low_u64_be(1),
+                    H160::from_low_u64_be(4)
+                ),
+                "log 1 topics differ".to_string(),
+                "log 1 data differs".to_string(),
+            ]
+        );
+    }
+}
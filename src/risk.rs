@@ -0,0 +1,418 @@
+use ethers::types::H160;
+use ethers::utils::id;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::constants::ZERO_ADDRESS;
+
+// Admin functions that let the deployer change the rules after people bought in
+pub static RISKY_SIGNATURES: &[&str] = &[
+    "mint(address,uint256)",
+    "blacklist(address)",
+    "setBlacklist(address,bool)",
+    "addBots(address[])",
+    "setFee(uint256)",
+    "setFees(uint256,uint256)",
+    "updateFees(uint256,uint256)",
+    "setTaxFeePercent(uint256)",
+    "setMaxTxAmount(uint256)",
+    "setSwapEnabled(bool)",
+    "setCooldownEnabled(bool)",
+    "pause()",
+];
+
+// Points added for each signal, the total is capped at 100
+const UNSWAPPABLE_POINTS: u8 = 100;
+const SELL_TAX_POINTS: u8 = 40;
+const BUY_TAX_POINTS: u8 = 25;
+const TRANSFER_TAX_POINTS: u8 = 15;
+// taxes below the threshold scale up to this
+const LOW_TAX_POINTS: f64 = 10.0;
+const PROXY_POINTS: u8 = 15;
+const OWNER_POINTS: u8 = 10;
+const FUNDED_ADMIN_POINTS: u8 = 10;
+const RISKY_SELECTOR_POINTS: u8 = 10;
+const MAX_RISKY_SELECTOR_POINTS: u8 = 30;
+const LOW_LIQUIDITY_POINTS: u8 = 15;
+const UNKNOWN_LIQUIDITY_POINTS: u8 = 5;
+const NEW_TOKEN_POINTS: u8 = 10;
+
+// Limits of a trading strategy, a sniper tolerates far more than a long-term holder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiskThresholds {
+    // highest taxes accepted, as fractions
+    pub max_buy_tax: f64,
+    pub max_sell_tax: f64,
+    pub max_transfer_tax: f64,
+    // least wrapped native token in the pool, in whole units
+    pub min_liquidity: f64,
+    // least stablecoin in the pool when it is paired with one, in whole units
+    pub min_stable_liquidity: f64,
+    // youngest token accepted
+    pub min_age_secs: u64,
+    // tokens scoring this much or more are treated as honeypots
+    pub max_score: u8,
+}
+
+impl Default for RiskThresholds {
+    fn default() -> Self {
+        Self {
+            max_buy_tax: 0.1,
+            max_sell_tax: 0.1,
+            max_transfer_tax: 0.1,
+            min_liquidity: 1.0,
+            min_stable_liquidity: 2000.0,
+            min_age_secs: 86400,
+            max_score: 50,
+        }
+    }
+}
+
+impl RiskThresholds {
+    // Long-term holding: low taxes, deep and established pools only
+    pub fn conservative() -> Self {
+        Self {
+            max_buy_tax: 0.05,
+            max_sell_tax: 0.05,
+            max_transfer_tax: 0.05,
+            min_liquidity: 10.0,
+            min_stable_liquidity: 20000.0,
+            min_age_secs: 7 * 86400,
+            max_score: 30,
+        }
+    }
+
+    // Sniping fresh launches: taxes and young tokens are expected
+    pub fn aggressive() -> Self {
+        Self {
+            max_buy_tax: 0.25,
+            max_sell_tax: 0.25,
+            max_transfer_tax: 0.25,
+            min_liquidity: 0.5,
+            min_stable_liquidity: 1000.0,
+            min_age_secs: 0,
+            max_score: 70,
+        }
+    }
+
+    // Same limit for buy, sell and transfer taxes
+    pub fn set_max_tax(&mut self, max_tax: f64) {
+        self.max_buy_tax = max_tax;
+        self.max_sell_tax = max_tax;
+        self.max_transfer_tax = max_tax;
+    }
+
+    // Whether the pool holds less of its safe token than the strategy trades in
+    pub fn is_shallow(&self, liquidity: Liquidity) -> bool {
+        match liquidity {
+            Liquidity::Native(amount) => amount < self.min_liquidity,
+            Liquidity::Stable(amount) => amount < self.min_stable_liquidity,
+        }
+    }
+
+    pub fn for_strategy(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "conservative" => Some(Self::conservative()),
+            "aggressive" => Some(Self::aggressive()),
+            _ => None,
+        }
+    }
+}
+
+// The safe token side of a pool, in whole units
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liquidity {
+    // wrapped native token
+    Native(f64),
+    // one of the chain's stablecoins
+    Stable(f64),
+}
+
+// Everything the score is computed from, a None is a signal that could not be measured
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RiskSignals {
+    pub swappable: bool,
+    pub buy_tax: f64,
+    pub sell_tax: f64,
+    pub transfer_tax: f64,
+    pub is_proxy: bool,
+    // None when the token has no owner() at all
+    pub owner: Option<H160>,
+    // addresses stored in the token's first slots that hold ETH
+    pub funded_admins: Vec<H160>,
+    // signatures of RISKY_SIGNATURES found in the code (or the implementation's)
+    pub risky_selectors: Vec<String>,
+    pub liquidity: Option<Liquidity>,
+    pub age: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskReason {
+    pub points: u8,
+    pub reason: String,
+    // a limit of the strategy is broken, which rejects the token whatever its score
+    pub limit: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskScore {
+    // 0 (nothing suspicious) to 100 (certainly a honeypot)
+    pub score: u8,
+    // the score reached max_score or one of the reasons breaks a limit
+    pub is_honeypot: bool,
+    pub reasons: Vec<RiskReason>,
+}
+
+impl RiskScore {
+    pub fn new(signals: &RiskSignals, thresholds: &RiskThresholds) -> Self {
+        let mut reasons = Vec::new();
+        let mut add = |points: u8, reason: String, limit: bool| {
+            reasons.push(RiskReason { points, reason, limit })
+        };
+
+        if !signals.swappable {
+            add(UNSWAPPABLE_POINTS, "buying or selling failed".to_string(), true);
+        }
+        for (name, tax, max_tax, points) in [
+            ("sell", signals.sell_tax, thresholds.max_sell_tax, SELL_TAX_POINTS),
+            ("buy", signals.buy_tax, thresholds.max_buy_tax, BUY_TAX_POINTS),
+            ("transfer", signals.transfer_tax, thresholds.max_transfer_tax, TRANSFER_TAX_POINTS),
+        ] {
+            if tax >= max_tax {
+                add(points, format!("{name} tax of {:.2}%", tax * 100.0), true);
+            } else if tax > 0.0 {
+                let points = (tax / max_tax * LOW_TAX_POINTS) as u8;
+                if points > 0 {
+                    add(points, format!("{name} tax of {:.2}%", tax * 100.0), false);
+                }
+            }
+        }
+
+        if signals.is_proxy {
+            add(PROXY_POINTS, "upgradeable proxy, the logic can be replaced".to_string(), false);
+        }
+        if let Some(owner) = signals.owner {
+            if owner != *ZERO_ADDRESS {
+                add(OWNER_POINTS, format!("ownership is not renounced ({owner:?})"), false);
+            }
+        }
+        if !signals.funded_admins.is_empty() {
+            add(
+                FUNDED_ADMIN_POINTS,
+                format!("{} funded admin candidates in storage", signals.funded_admins.len()),
+                false,
+            );
+        }
+        if !signals.risky_selectors.is_empty() {
+            let points = (signals.risky_selectors.len() as u8)
+                .saturating_mul(RISKY_SELECTOR_POINTS)
+                .min(MAX_RISKY_SELECTOR_POINTS);
+            add(points, format!("admin functions: {}", signals.risky_selectors.join(", ")), false);
+        }
+
+        match signals.liquidity {
+            Some(liquidity) if thresholds.is_shallow(liquidity) => {
+                let reason = match liquidity {
+                    Liquidity::Native(amount) => {
+                        format!("only {amount:.4} of the native token in the pool")
+                    }
+                    Liquidity::Stable(amount) => {
+                        format!("only {amount:.2} of the stablecoin in the pool")
+                    }
+                };
+                add(LOW_LIQUIDITY_POINTS, reason, true)
+            }
+            Some(_) => {}
+            None => add(UNKNOWN_LIQUIDITY_POINTS, "pool liquidity is unknown".to_string(), false),
+        }
+        if let Some(age) = signals.age {
+            if age.as_secs() < thresholds.min_age_secs {
+                add(NEW_TOKEN_POINTS, format!("deployed {} hours ago", age.as_secs() / 3600), true);
+            }
+        }
+
+        let total: u32 = reasons.iter().map(|reason| reason.points as u32).sum();
+        let score = total.min(100) as u8;
+        let is_honeypot =
+            score >= thresholds.max_score || reasons.iter().any(|reason| reason.limit);
+        Self { score, is_honeypot, reasons }
+    }
+}
+
+// Selectors pushed by the dispatcher (PUSH4), skipping the data of every other PUSH
+pub fn find_selectors(code: &[u8], signatures: &[&str]) -> Vec<String> {
+    let mut pushed = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        if op == 0x63 && pc + 5 <= code.len() {
+            pushed.push([code[pc + 1], code[pc + 2], code[pc + 3], code[pc + 4]]);
+        }
+        pc += match op {
+            0x60..=0x7f => (op - 0x5f) as usize + 1,
+            _ => 1,
+        };
+    }
+
+    signatures
+        .iter()
+        .filter(|signature| pushed.contains(&id(signature)))
+        .map(|signature| signature.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A swappable token in a deep pool with nothing else to report
+    fn clean_signals() -> RiskSignals {
+        RiskSignals {
+            swappable: true,
+            liquidity: Some(Liquidity::Native(100.0)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clean_token_scores_zero() {
+        let risk = RiskScore::new(&clean_signals(), &RiskThresholds::default());
+        assert_eq!(risk.score, 0);
+        assert!(!risk.is_honeypot);
+        assert!(risk.reasons.is_empty());
+    }
+
+    #[test]
+    fn score_is_capped_at_100() {
+        let signals = RiskSignals {
+            swappable: false,
+            sell_tax: 0.5,
+            buy_tax: 0.5,
+            is_proxy: true,
+            owner: Some(H160::repeat_byte(1)),
+            liquidity: None,
+            ..clean_signals()
+        };
+        let risk = RiskScore::new(&signals, &RiskThresholds::default());
+        assert_eq!(risk.score, 100);
+        assert!(risk.is_honeypot);
+        assert!(risk.reasons.iter().map(|reason| reason.points as u32).sum::<u32>() > 100);
+    }
+
+    #[test]
+    fn soft_signals_only_count_towards_the_score() {
+        let signals = RiskSignals {
+            buy_tax: 0.05,
+            is_proxy: true,
+            owner: Some(H160::repeat_byte(1)),
+            liquidity: None,
+            ..clean_signals()
+        };
+        let risk = RiskScore::new(&signals, &RiskThresholds::default());
+        assert_eq!(risk.score, 5 + PROXY_POINTS + OWNER_POINTS + UNKNOWN_LIQUIDITY_POINTS);
+        assert!(!risk.is_honeypot);
+        assert!(risk.reasons.iter().all(|reason| !reason.limit));
+    }
+
+    #[test]
+    fn renounced_ownership_is_not_a_signal() {
+        let signals = RiskSignals { owner: Some(*ZERO_ADDRESS), ..clean_signals() };
+        assert_eq!(RiskScore::new(&signals, &RiskThresholds::default()).score, 0);
+    }
+
+    #[test]
+    fn presets_by_name() {
+        assert_eq!(RiskThresholds::for_strategy("default").unwrap().max_score, 50);
+        assert_eq!(RiskThresholds::for_strategy("conservative").unwrap().max_score, 30);
+        assert_eq!(RiskThresholds::for_strategy("aggressive").unwrap().max_score, 70);
+        assert!(RiskThresholds::for_strategy("yolo").is_none());
+    }
+
+    #[test]
+    fn verdict_depends_on_the_strategy() {
+        // 20% sell tax: over the limit of conservative and default, tolerated by aggressive
+        let signals = RiskSignals { sell_tax: 0.2, ..clean_signals() };
+
+        let conservative = RiskScore::new(&signals, &RiskThresholds::conservative());
+        assert_eq!(conservative.score, SELL_TAX_POINTS);
+        assert!(conservative.is_honeypot);
+
+        // the score stays under max_score, the broken tax limit alone rejects the token
+        let default = RiskScore::new(&signals, &RiskThresholds::default());
+        assert_eq!(default.score, SELL_TAX_POINTS);
+        assert!(default.is_honeypot);
+
+        let aggressive = RiskScore::new(&signals, &RiskThresholds::aggressive());
+        assert_eq!(aggressive.score, 8);
+        assert!(!aggressive.is_honeypot);
+    }
+
+    #[test]
+    fn young_and_shallow_tokens() {
+        let signals = RiskSignals {
+            liquidity: Some(Liquidity::Native(0.8)),
+            age: Some(Duration::from_secs(3600)),
+            ..clean_signals()
+        };
+        let default = RiskScore::new(&signals, &RiskThresholds::default());
+        assert_eq!(default.score, LOW_LIQUIDITY_POINTS + NEW_TOKEN_POINTS);
+        assert!(default.is_honeypot);
+        assert!(default.reasons.iter().all(|reason| reason.limit));
+
+        let aggressive = RiskScore::new(&signals, &RiskThresholds::aggressive());
+        assert_eq!(aggressive.score, 0);
+        assert!(!aggressive.is_honeypot);
+    }
+
+    #[test]
+    fn stablecoin_pools_have_their_own_minimum() {
+        let thresholds = RiskThresholds::default();
+        assert!(thresholds.is_shallow(Liquidity::Stable(100.0)));
+        assert!(!thresholds.is_shallow(Liquidity::Stable(5000.0)));
+
+        let signals = RiskSignals { liquidity: Some(Liquidity::Stable(100.0)), ..clean_signals() };
+        assert_eq!(RiskScore::new(&signals, &thresholds).score, LOW_LIQUIDITY_POINTS);
+    }
+
+    #[test]
+    fn finds_dispatched_selectors() {
+        let pause = id("pause()");
+        let mut code = vec![0x60, 0x80, 0x63];
+        code.extend(pause);
+        code.push(0x14);
+
+        assert_eq!(find_selectors(&code, RISKY_SIGNATURES), vec!["pause()".to_string()]);
+    }
+
+    #[test]
+    fn skips_push_data() {
+        // the selector only appears inside the data of a PUSH32
+        let mut code = vec![0x7f, 0x00, 0x63];
+        code.extend(id("pause()"));
+        code.extend([0u8; 26]);
+        // truncated PUSH4 at the end of the code
+        code.extend([0x63, 0x01]);
+
+        assert!(find_selectors(&code, RISKY_SIGNATURES).is_empty());
+    }
+
+    #[test]
+    fn caps_risky_selector_points() {
+        let mut code = Vec::new();
+        for signature in RISKY_SIGNATURES {
+            code.push(0x63);
+            code.extend(id(signature));
+        }
+        let risky_selectors = find_selectors(&code, RISKY_SIGNATURES);
+        assert_eq!(risky_selectors.len(), RISKY_SIGNATURES.len());
+
+        let signals = RiskSignals { risky_selectors, ..clean_signals() };
+        assert_eq!(
+            RiskScore::new(&signals, &RiskThresholds::default()).score,
+            MAX_RISKY_SELECTOR_POINTS
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::{anyhow, Result};
+use axum::{extract::State, routing::post, Json, Router};
+use ethers::providers::Middleware;
+use ethers::types::{Bytes, H160, U256, U64};
+use futures::StreamExt;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::Mutex;
+
+use crate::constants::{ChainConfig, DEFAULT_GAS_LIMIT};
+use crate::honeypot::{HoneypotConfig, HoneypotFilter};
+use crate::pools::{find_pool, Pool};
+use crate::simulator::{BlockOverrides, EvmSimulator, RollMode, StateOverride, Tx, TxResult};
+
+// JSON-RPC 2.0 error codes
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(e: impl std::fmt::Display) -> Self {
+        Self { code: INVALID_PARAMS, message: format!("Invalid params: {e}") }
+    }
+
+    fn internal(e: impl std::fmt::Debug) -> Self {
+        Self { code: INTERNAL_ERROR, message: format!("{e:?}") }
+    }
+}
+
+// A call in the same shape as eth_call's transaction object
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallRequest {
+    pub from: Option<H160>,
+    pub to: H160,
+    #[serde(alias = "input")]
+    pub data: Option<Bytes>,
+    pub value: Option<U256>,
+    pub gas: Option<U64>,
+    pub gas_price: Option<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+impl CallRequest {
+    fn into_tx(self, default_caller: H160) -> Tx {
+        Tx {
+            caller: self.from.unwrap_or(default_caller),
+            transact_to: self.to,
+            data: self.data.unwrap_or_default().0,
+            value: self.value.unwrap_or_default(),
+            gas_limit: self.gas.map(|gas| gas.as_u64()).unwrap_or(DEFAULT_GAS_LIMIT),
+            gas_price: self.max_fee_per_gas.or(self.gas_price).unwrap_or_default(),
+            gas_priority_fee: self.max_priority_fee_per_gas,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallResult {
+    pub success: bool,
+    pub output: Bytes,
+    pub gas_used: u64,
+    pub error: Option<String>,
+}
+
+impl From<Result<TxResult>> for CallResult {
+    fn from(result: Result<TxResult>) -> Self {
+        match result {
+            Ok(result) => Self {
+                success: true,
+                output: result.output.into(),
+                gas_used: result.gas_used,
+                error: None,
+            },
+            Err(e) => Self {
+                success: false,
+                output: Bytes::default(),
+                gas_used: 0,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+pub struct ServerState<M> {
+    pub filter: Mutex<HoneypotFilter<M>>,
+    pub pools: Vec<Pool>,
+}
+
+impl<M: Middleware + 'static> ServerState<M> {
+    // Every simulation runs on a copy of the warm simulator so requests never leak state
+    async fn fork(&self) -> EvmSimulator<M> {
+        self.filter.lock().await.simulator.clone()
+    }
+
+    async fn honeypot_check(&self, params: &[Value]) -> Result<Value, RpcError> {
+        let token: H160 = param(params, 0)?.ok_or(RpcError::invalid_params("missing token"))?;
+        let pool: Option<H160> = param(params, 1)?;
+
+        // The checks commit swaps and wait on the RPC, run them on a copy outside of the lock
+        let mut filter = self.filter.lock().await.clone();
+        let pool = match pool {
+            Some(pool) => pool,
+            None => find_pool(&self.pools, token, filter.safe_tokens.weth)
+                .map(|pool| pool.address)
+                .ok_or(RpcError::invalid_params(format!("no WETH pool found for {token:?}")))?,
+        };
+
+        let check = filter.check_token(token, pool).await;
+
+        serde_json::to_value(check.map_err(RpcError::internal)?).map_err(RpcError::internal)
+    }
+
+    async fn simulate_call(&self, params: &[Value]) -> Result<Value, RpcError> {
+        let call: CallRequest = param(params, 0)?.ok_or(RpcError::invalid_params("missing tx"))?;
+        let block_overrides: Option<BlockOverrides> = param(params, 1)?;
+        let state_overrides: Option<StateOverride> = param(params, 2)?;
+
+        let mut simulator = self.fork().await;
+        simulator.apply_block_overrides(&block_overrides.unwrap_or_default());
+        simulator
+            .apply_state_override(&state_overrides.unwrap_or_default())
+            .map_err(RpcError::internal)?;
+
+        let tx = call.into_tx(simulator.owner);
+        let result = CallResult::from(simulator.staticcall(tx));
+        serde_json::to_value(result).map_err(RpcError::internal)
+    }
+
+    async fn simulate_bundle(&self, params: &[Value]) -> Result<Value, RpcError> {
+        let calls: Vec<CallRequest> =
+            param(params, 0)?.ok_or(RpcError::invalid_params("missing txs"))?;
+        let block_overrides: Option<BlockOverrides> = param(params, 1)?;
+        let state_overrides: Option<StateOverride> = param(params, 2)?;
+
+        let mut simulator = self.fork().await;
+        simulator.apply_block_overrides(&block_overrides.unwrap_or_default());
+        simulator
+            .apply_state_override(&state_overrides.unwrap_or_default())
+            .map_err(RpcError::internal)?;
+
+        // Transactions are committed one after another, a failing one doesn't stop the bundle
+        let results: Vec<CallResult> = calls
+            .into_iter()
+            .map(|call| {
+                let tx = call.into_tx(simulator.owner);
+                CallResult::from(simulator.call(tx))
+            })
+            .collect();
+        serde_json::to_value(results).map_err(RpcError::internal)
+    }
+
+    async fn estimate_gas(&self, params: &[Value]) -> Result<Value, RpcError> {
+        let call: CallRequest = param(params, 0)?.ok_or(RpcError::invalid_params("missing tx"))?;
+        let block_overrides: Option<BlockOverrides> = param(params, 1)?;
+        let state_overrides: Option<StateOverride> = param(params, 2)?;
+
+        let mut simulator = self.fork().await;
+        simulator.apply_block_overrides(&block_overrides.unwrap_or_default());
+        simulator
+            .apply_state_override(&state_overrides.unwrap_or_default())
+            .map_err(RpcError::internal)?;
+
+        let tx = call.into_tx(simulator.owner);
+        let gas = simulator.estimate_gas(tx).map_err(RpcError::internal)?;
+        Ok(json!(U64::from(gas)))
+    }
+
+    async fn dispatch(&self, request: JsonRpcRequest) -> Result<Value, RpcError> {
+        let params = match request.params {
+            Value::Array(params) => params,
+            Value::Null => vec![],
+            _ => return Err(RpcError::invalid_params("params must be an array")),
+        };
+
+        match request.method.as_str() {
+            "honeypot_check" => self.honeypot_check(&params).await,
+            "simulate_call" => self.simulate_call(&params).await,
+            "simulate_bundle" => self.simulate_bundle(&params).await,
+            "simulate_estimateGas" => self.estimate_gas(&params).await,
+            "simulate_blockNumber" => {
+                let block_number = self.filter.lock().await.simulator.block_number;
+                Ok(json!(block_number))
+            }
+            method => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Method not found: {method}"),
+            }),
+        }
+    }
+}
+
+fn param<T: serde::de::DeserializeOwned>(
+    params: &[Value],
+    idx: usize,
+) -> Result<Option<T>, RpcError> {
+    match params.get(idx) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => {
+            serde_json::from_value(value.clone()).map(Some).map_err(RpcError::invalid_params)
+        }
+    }
+}
+
+async fn handle<M: Middleware + 'static>(
+    State(state): State<Arc<ServerState<M>>>,
+    Json(body): Json<Value>,
+) -> Json<Value> {
+    let request: JsonRpcRequest = match serde_json::from_value(body) {
+        Ok(request) => request,
+        Err(e) => {
+            return Json(json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": { "code": INVALID_REQUEST, "message": e.to_string() },
+            }))
+        }
+    };
+
+    let id = request.id.clone();
+    let response = match state.dispatch(request).await {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    };
+    Json(response)
+}
+
+// Re-forks the warm filter every time a new block comes in
+async fn follow_head<M: Middleware + 'static>(
+    state: Arc<ServerState<M>>,
+    provider: Arc<M>,
+) -> Result<()> {
+    let mut blocks =
+        provider.watch_blocks().await.map_err(|e| anyhow!("Failed to watch new blocks: {e:?}"))?;
+
+    while let Some(block_hash) = blocks.next().await {
+        let block_number = match provider.get_block(block_hash).await {
+            Ok(Some(block)) => match block.number {
+                Some(block_number) => block_number,
+                None => continue,
+            },
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Failed to fetch block {block_hash:?}: {e:?}");
+                continue;
+            }
+        };
+
+        // Roll a copy so requests keep being served while the touched accounts are collected,
+        // roll_to moves it onto a new backend so in-flight checks stay on the old block
+        let mut simulator = state.filter.lock().await.simulator.clone();
+        if let Err(e) = simulator.roll_to(block_number, RollMode::Touched).await {
+            warn!("Failed to roll to block {block_number}: {e:?}");
+            continue;
+        }
+
+        let mut filter = state.filter.lock().await;
+        filter.simulator = simulator;
+        filter.reset_results();
+        info!("Simulator moved to block {}", filter.simulator.block_number);
+    }
+
+    Ok(())
+}
+
+pub async fn serve<M: Middleware + 'static>(
+    provider: Arc<M>,
+    chain: ChainConfig,
+    config: HoneypotConfig,
+    pools: Vec<Pool>,
+    addr: SocketAddr,
+) -> Result<()> {
+    let filter = HoneypotFilter::from_latest_block(provider.clone(), chain, config).await?;
+    let state = Arc::new(ServerState { filter: Mutex::new(filter), pools });
+
+    let head_state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = follow_head(head_state, provider).await {
+            warn!("Stopped following new blocks: {e:?}");
+        }
+    });
+
+    let app = Router::new().route("/", post(handle::<M>)).with_state(state);
+
+    info!("JSON-RPC server listening on {addr}");
+    axum::Server::bind(&addr).serve(app.into_make_service()).await?;
+
+    Ok(())
+}
@@ -2,44 +2,144 @@ use alloy_primitives::{Address, B256, U256 as aU256};
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
 use ethers::abi;
-use ethers::types::{Transaction, H160, U256, U64};
+use ethers::types::transaction::eip2930::AccessList;
+use ethers::types::{
+    Block, BlockNumber, Filter, TraceType, Transaction, H160, H256, U256, U512, U64,
+};
 use ethers_providers::Middleware;
 use foundry_common::types::{ToAlloy, ToEthers};
 use foundry_evm::{
     fork::{BlockchainDb, BlockchainDbMeta, SharedBackend},
     revm::{
-        db::{CacheDB, Database},
+        db::{CacheDB, Database, DatabaseCommit, DatabaseRef},
+        interpreter::InstructionResult,
         primitives::{
-            keccak256, AccountInfo, Bytecode, ExecutionResult, Output, TransactTo, KECCAK_EMPTY,
-            U256 as rU256,
+            keccak256, AccountInfo, Bytecode, CreateScheme, ExecutionResult, Output,
+            ResultAndState, SpecId, TransactTo, KECCAK_EMPTY, U256 as rU256,
         },
         EVM,
     },
 };
-use std::{collections::BTreeSet, str::FromStr, sync::Arc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 use thiserror::Error;
 
-use crate::constants::{IMPLEMENTATION_SLOTS, SIMULATOR_CODE};
+use crate::compliance::{CheckVerdict, ComplianceCheck, ComplianceReport};
+use crate::constants::{
+    ChainConfig, APPROVED_SPENDER, DEAD_ADDRESS, DEFAULT_GAS_LIMIT, HOLDER_LOOKBACK_BLOCKS,
+    IMPLEMENTATION_SLOTS, SIMULATOR_CODE, TRANSFER_RECIPIENT, TRANSFER_TOPIC, TX_BASE_GAS, WEI,
+    ZERO_ADDRESS,
+};
+use crate::fixtures::StateFixture;
+use crate::inspectors::{
+    access_list::AccessListInspector,
+    call_tracer::{decode_revert_reason, CallFrame, CallTracer},
+    storage::StorageRecorder,
+};
 use crate::interfaces::ownable::OwnableABI;
-use crate::interfaces::{pool::V2PoolABI, simulator::SimulatorABI, token::TokenABI};
+use crate::interfaces::{
+    factory::V2FactoryABI, pool::V2PoolABI, router::V2RouterABI, simulator::SimulatorABI,
+    token::TokenABI,
+};
+use crate::mempool::decode_effects;
+use crate::tax::bps;
 use crate::tokens::get_token_info;
 use crate::trace::EvmTracer;
+use crate::transfer::{BalanceDelta, TrackedBalances, TransferAnalysis};
 
-#[derive(Clone)]
-pub struct EvmSimulator<M> {
+// Generic over the database behind the CacheDB so that it can also run on a pure in-memory
+// backend (see fixtures), forking a live chain through a SharedBackend is the default
+pub struct EvmSimulator<M, DB: DatabaseRef = SharedBackend> {
     pub provider: Arc<M>,
     pub owner: H160,
-    pub evm: EVM<CacheDB<SharedBackend>>,
+    pub evm: EVM<CacheDB<DB>>,
     pub block_number: U64,
 
+    // Handles to the state cache, roll_to carries it over to the backend of the new block
+    // blockchain_db is only set for forks of a live chain
+    pub backend: DB,
+    pub blockchain_db: Option<BlockchainDb>,
+
+    // Applied on top of the header every time the block env is (re)loaded
+    pub block_overrides: BlockOverrides,
+    // Attach the access list and state diff of every call to its TxResult
+    pub record_access: bool,
+
     pub token: TokenABI,
     pub v2_pool: V2PoolABI,
+    pub v2_router: V2RouterABI,
+    pub v2_factory: V2FactoryABI,
     pub simulator: SimulatorABI,
     pub ownable: OwnableABI,
 
     pub simulator_address: H160,
 }
 
+// Not derived: that would require M: Clone, while only the Arc around the provider is copied
+impl<M, DB: DatabaseRef + Clone> Clone for EvmSimulator<M, DB> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            owner: self.owner,
+            evm: self.evm.clone(),
+            block_number: self.block_number,
+            backend: self.backend.clone(),
+            blockchain_db: self.blockchain_db.clone(),
+            block_overrides: self.block_overrides.clone(),
+            record_access: self.record_access,
+            token: self.token.clone(),
+            v2_pool: self.v2_pool.clone(),
+            v2_router: self.v2_router.clone(),
+            v2_factory: self.v2_factory.clone(),
+            simulator: self.simulator.clone(),
+            ownable: self.ownable.clone(),
+            simulator_address: self.simulator_address,
+        }
+    }
+}
+
+// geth's eth_call block overrides, every field left empty keeps the value from the header
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockOverrides {
+    pub number: Option<U64>,
+    pub difficulty: Option<U256>,
+    pub time: Option<U64>,
+    pub gas_limit: Option<U64>,
+    pub coinbase: Option<H160>,
+    pub random: Option<H256>,
+    pub base_fee: Option<U256>,
+}
+
+// geth's eth_call account override, `state` replaces the whole storage while `stateDiff` patches slots
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<U64>,
+    pub code: Option<ethers::types::Bytes>,
+    pub state: Option<HashMap<H256, H256>>,
+    pub state_diff: Option<HashMap<H256, H256>>,
+}
+
+pub type StateOverride = HashMap<H160, AccountOverride>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RollMode {
+    // Drop every cached account and slot, including local modifications (deployed simulator, balances)
+    InvalidateAll,
+    // Only drop the accounts touched by the blocks rolled over, needs the trace namespace and
+    // falls back to InvalidateAll without it
+    #[default]
+    Touched,
+}
+
 #[derive(Debug, Clone)]
 pub struct Tx {
     pub caller: H160,
@@ -47,6 +147,9 @@ pub struct Tx {
     pub data: Bytes,
     pub value: U256,
     pub gas_limit: u64,
+    // Max fee per gas for EIP-1559 txs when gas_priority_fee is set, the legacy gas price otherwise
+    pub gas_price: U256,
+    pub gas_priority_fee: Option<U256>,
 }
 
 #[derive(Debug, Clone)]
@@ -54,8 +157,22 @@ pub struct TxResult {
     pub output: Bytes,
     pub gas_used: u64,
     pub gas_refunded: u64,
+    // Only filled when the simulator runs with record_access
+    pub access_list: Option<AccessList>,
+    pub state_diff: Option<StateDiff>,
+}
+
+// Pre/post values of everything a transaction changed in an account
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AccountDiff {
+    pub balance: Option<(U256, U256)>,
+    pub nonce: Option<(u64, u64)>,
+    pub code_changed: bool,
+    pub storage: BTreeMap<H256, (H256, H256)>,
 }
 
+pub type StateDiff = BTreeMap<H160, AccountDiff>;
+
 #[derive(Debug, Clone)]
 pub struct SimpleTransferResult {
     pub transfered_amount: U256,
@@ -68,34 +185,280 @@ pub enum SimpleTransferError {
     TxFailed(anyhow::Error),
 }
 
+// How a bool-returning token call failed, mirroring SafeERC20's errors
+#[derive(Error, Debug)]
+pub enum TokenCallError {
+    #[error("Token call reverted: {0}")]
+    Reverted(String),
+    #[error("Token {0:?} has no code")]
+    NoCode(H160),
+}
+
 #[derive(Error, Debug)]
 pub enum SwapError {
     #[error("Swap call failed: {0}")]
     TxFailed(anyhow::Error),
 }
 
+// Storage layout of UniswapV2Pair's reserve slot: reserve0 (112) | reserve1 (112) | blockTimestampLast (32)
+pub fn pack_v2_reserves(reserve0: u128, reserve1: u128, block_timestamp_last: u32) -> rU256 {
+    rU256::from(reserve0)
+        | (rU256::from(reserve1) << 112usize)
+        | (rU256::from(block_timestamp_last) << 224usize)
+}
+
+// EIP-1559 base fee of the block after the parent (elasticity 2, max change 1/8)
+pub fn next_base_fee(gas_used: U256, gas_limit: U256, base_fee: U256) -> U256 {
+    let gas_target = gas_limit / 2;
+    if gas_target.is_zero() || gas_used == gas_target {
+        return base_fee;
+    }
+
+    if gas_used > gas_target {
+        let delta = base_fee * (gas_used - gas_target) / gas_target / 8;
+        base_fee + delta.max(U256::one())
+    } else {
+        let delta = base_fee * (gas_target - gas_used) / gas_target / 8;
+        base_fee.saturating_sub(delta)
+    }
+}
+
+// Hardfork rules of a historical block, chains without a schedule here run the latest rules
+pub fn spec_id_at(chain_id: u64, block_number: u64, timestamp: u64) -> SpecId {
+    match chain_id {
+        // Ethereum mainnet, forks after the merge activate by timestamp
+        1 => match (block_number, timestamp) {
+            (_, t) if t >= 1_710_338_135 => SpecId::CANCUN,
+            (_, t) if t >= 1_681_338_455 => SpecId::SHANGHAI,
+            (n, _) if n >= 15_537_394 => SpecId::MERGE,
+            (n, _) if n >= 15_050_000 => SpecId::GRAY_GLACIER,
+            (n, _) if n >= 13_773_000 => SpecId::ARROW_GLACIER,
+            (n, _) if n >= 12_965_000 => SpecId::LONDON,
+            (n, _) if n >= 12_244_000 => SpecId::BERLIN,
+            (n, _) if n >= 9_200_000 => SpecId::MUIR_GLACIER,
+            (n, _) if n >= 9_069_000 => SpecId::ISTANBUL,
+            (n, _) if n >= 7_280_000 => SpecId::PETERSBURG,
+            (n, _) if n >= 4_370_000 => SpecId::BYZANTIUM,
+            (n, _) if n >= 2_675_000 => SpecId::SPURIOUS_DRAGON,
+            (n, _) if n >= 2_463_000 => SpecId::TANGERINE,
+            (n, _) if n >= 1_920_000 => SpecId::DAO_FORK,
+            (n, _) if n >= 1_150_000 => SpecId::HOMESTEAD,
+            _ => SpecId::FRONTIER,
+        },
+        // BNB Smart Chain: Hertz (Berlin + London), Kepler (Shanghai), Tycho (Cancun)
+        56 => match (block_number, timestamp) {
+            (_, t) if t >= 1_718_863_500 => SpecId::CANCUN,
+            (_, t) if t >= 1_705_996_800 => SpecId::SHANGHAI,
+            (n, _) if n >= 31_302_048 => SpecId::LONDON,
+            _ => SpecId::MUIR_GLACIER,
+        },
+        // Base: Bedrock from genesis, Canyon (Shanghai), Ecotone (Cancun)
+        8453 => match timestamp {
+            t if t >= 1_710_374_401 => SpecId::CANCUN,
+            t if t >= 1_704_992_401 => SpecId::SHANGHAI,
+            _ => SpecId::MERGE,
+        },
+        _ => SpecId::LATEST,
+    }
+}
+
 impl<M: Middleware + 'static> EvmSimulator<M> {
     pub fn new(provider: Arc<M>, owner: H160, block_number: U64) -> Self {
+        let blockchain_db = Self::empty_blockchain_db();
         let shared_backend = SharedBackend::spawn_backend_thread(
             provider.clone(),
-            BlockchainDb::new(
-                BlockchainDbMeta {
-                    cfg_env: Default::default(),
-                    block_env: Default::default(),
-                    hosts: BTreeSet::from(["".to_string()]),
-                },
-                None,
-            ),
+            blockchain_db.clone(),
             Some(block_number.into()),
         );
-        let db = CacheDB::new(shared_backend);
 
+        let mut simulator = Self::from_db(provider, owner, block_number, shared_backend);
+        simulator.blockchain_db = Some(blockchain_db);
+        simulator
+    }
+
+    fn empty_blockchain_db() -> BlockchainDb {
+        BlockchainDb::new(
+            BlockchainDbMeta {
+                cfg_env: Default::default(),
+                block_env: Default::default(),
+                hosts: BTreeSet::from(["".to_string()]),
+            },
+            None,
+        )
+    }
+
+    // Moves the fork to a newer block on a fresh backend, carrying over the cached state of every
+    // account that did not change in between. The old backend stays pinned to the old block, so
+    // clones taken before the roll keep reading a single block
+    pub async fn roll_to(&mut self, block_number: U64, mode: RollMode) -> Result<()> {
+        if block_number < self.block_number {
+            return Err(anyhow!(
+                "Cannot roll back from block {} to {}",
+                self.block_number,
+                block_number
+            ));
+        }
+        if block_number == self.block_number {
+            return Ok(());
+        }
+
+        let old_db =
+            self.blockchain_db.clone().ok_or(anyhow!("Simulator is not forked from a chain"))?;
+        let touched = match mode {
+            RollMode::InvalidateAll => None,
+            RollMode::Touched => {
+                self.touched_accounts(self.block_number + U64::one(), block_number).await?
+            }
+        };
+
+        let blockchain_db = Self::empty_blockchain_db();
+        if let Some(touched) = &touched {
+            let touched: HashSet<_> = touched.iter().map(|address| address.to_alloy()).collect();
+            blockchain_db.accounts().write().extend(
+                old_db
+                    .accounts()
+                    .read()
+                    .iter()
+                    .filter(|(address, _)| !touched.contains(*address))
+                    .map(|(address, info)| (*address, info.clone())),
+            );
+            blockchain_db.storage().write().extend(
+                old_db
+                    .storage()
+                    .read()
+                    .iter()
+                    .filter(|(address, _)| !touched.contains(*address))
+                    .map(|(address, slots)| (*address, slots.clone())),
+            );
+            blockchain_db
+                .block_hashes()
+                .write()
+                .extend(old_db.block_hashes().read().iter().map(|(number, hash)| (*number, *hash)));
+        }
+
+        let backend = SharedBackend::spawn_backend_thread(
+            self.provider.clone(),
+            blockchain_db.clone(),
+            Some(block_number.into()),
+        );
+
+        match touched {
+            None => self.evm.database(CacheDB::new(backend.clone())),
+            Some(touched) => {
+                let db = self.evm.db.as_mut().unwrap();
+                for address in touched {
+                    db.accounts.remove(&address.to_alloy());
+                }
+                db.db = backend.clone();
+            }
+        }
+
+        self.backend = backend;
+        self.blockchain_db = Some(blockchain_db);
+        self.block_number = block_number;
+        self.load_block_env().await
+    }
+
+    // Dumps everything fetched from the RPC so far (local modifications excluded) together with
+    // the block env, StateFixture::replay brings it back without a provider
+    pub fn record_state(&self) -> Result<StateFixture> {
+        let blockchain_db =
+            self.blockchain_db.as_ref().ok_or(anyhow!("Simulator is not forked from a chain"))?;
+
+        let mut accounts = StateOverride::new();
+        for (address, info) in blockchain_db.accounts().read().iter() {
+            let code = info
+                .code
+                .as_ref()
+                .map(|code| code.original_bytes())
+                .filter(|code| !code.is_empty())
+                .map(|code| code.to_vec().into());
+            accounts.insert(
+                address.to_ethers(),
+                AccountOverride {
+                    balance: Some(info.balance.to_ethers()),
+                    nonce: Some(U64::from(info.nonce)),
+                    code,
+                    state: Some(HashMap::new()),
+                    state_diff: None,
+                },
+            );
+        }
+        for (address, slots) in blockchain_db.storage().read().iter() {
+            let account = accounts.entry(address.to_ethers()).or_default();
+            let state = account.state.get_or_insert_with(HashMap::new);
+            for (slot, value) in slots {
+                state.insert(
+                    H256::from(slot.to_be_bytes::<32>()),
+                    H256::from(value.to_be_bytes::<32>()),
+                );
+            }
+        }
+        let block_hashes = blockchain_db
+            .block_hashes()
+            .read()
+            .iter()
+            .map(|(number, hash)| (number.to::<u64>(), hash.to_ethers()))
+            .collect();
+
+        Ok(StateFixture {
+            chain_id: self.evm.env.cfg.chain_id,
+            block_number: self.block_number,
+            block: self.current_block_overrides(),
+            accounts,
+            block_hashes,
+        })
+    }
+
+    // Collects the accounts changed in [from, to], using the state diffs of trace_replayBlockTransactions
+    // None when the node has no trace namespace: internal storage writes can't be seen without it,
+    // so the caller has to invalidate everything
+    async fn touched_accounts(&self, from: U64, to: U64) -> Result<Option<HashSet<H160>>> {
+        let mut touched = HashSet::new();
+
+        for number in from.as_u64()..=to.as_u64() {
+            let traces = self
+                .provider
+                .trace_replay_block_transactions(
+                    BlockNumber::Number(number.into()),
+                    vec![TraceType::StateDiff],
+                )
+                .await;
+            match traces {
+                Ok(traces) => {
+                    for trace in traces {
+                        if let Some(state_diff) = trace.state_diff {
+                            touched.extend(state_diff.0.into_keys());
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("No state diff for block {number}, invalidating the whole cache: {e:?}");
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(touched))
+    }
+}
+
+impl<M, DB> EvmSimulator<M, DB>
+where
+    M: Middleware + 'static,
+    DB: DatabaseRef + Clone,
+    DB::Error: std::error::Error + Send + Sync + 'static,
+{
+    // Simulates on top of any database, e.g. CacheDB<EmptyDB> for offline fixtures
+    pub fn from_db(provider: Arc<M>, owner: H160, block_number: U64, db: DB) -> Self {
         let mut evm = EVM::new();
-        evm.database(db);
+        evm.database(CacheDB::new(db.clone()));
 
         evm.env.cfg.limit_contract_code_size = Some(0x100000);
         evm.env.cfg.disable_block_gas_limit = true;
         evm.env.cfg.disable_base_fee = true;
+        // Lets contracts (pairs, holders) be used as the caller
+        evm.env.cfg.disable_eip3607 = true;
 
         evm.env.block.number = rU256::from(block_number.as_u64() + 1);
 
@@ -105,8 +468,16 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             evm,
             block_number,
 
+            backend: db,
+            blockchain_db: None,
+
+            block_overrides: BlockOverrides::default(),
+            record_access: false,
+
             token: TokenABI::new(),
             v2_pool: V2PoolABI::new(),
+            v2_router: V2RouterABI::new(),
+            v2_factory: V2FactoryABI::new(),
             simulator: SimulatorABI::new(),
             ownable: OwnableABI::new(),
 
@@ -115,78 +486,363 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
         }
     }
 
-    pub fn inject_db(&mut self, db: CacheDB<SharedBackend>) {
+    pub fn inject_db(&mut self, db: CacheDB<DB>) {
         self.evm.database(db);
     }
 
-    pub fn run_pending_tx(&mut self, tx: &Transaction) -> Result<TxResult> {
-        // We simply need to commit changes to the DB
+    // Executes in the environment of `block` itself, used to re-execute its transactions
+    pub fn apply_header<TX>(&mut self, block: &Block<TX>) {
+        let block_env = &mut self.evm.env.block;
+        if let Some(number) = block.number {
+            block_env.number = rU256::from(number.as_u64());
+        }
+        block_env.timestamp = block.timestamp.to_alloy();
+        block_env.gas_limit = block.gas_limit.to_alloy();
+        block_env.difficulty = block.difficulty.to_alloy();
+        block_env.prevrandao = block.mix_hash.map(|mix_hash| mix_hash.to_alloy());
+        if let Some(author) = block.author {
+            block_env.coinbase = author.to_alloy();
+        }
+        if let Some(base_fee) = block.base_fee_per_gas {
+            block_env.basefee = base_fee.to_alloy();
+        }
+    }
+
+    // Picks the hardfork rules from the chain id and the block of the env, needed to replay
+    // historical blocks (e.g. pre-Shanghai blocks must not know PUSH0)
+    pub fn apply_spec(&mut self) {
+        let env = &mut self.evm.env;
+        env.cfg.spec_id = spec_id_at(
+            env.cfg.chain_id,
+            env.block.number.saturating_to::<u64>(),
+            env.block.timestamp.saturating_to::<u64>(),
+        );
+    }
+
+    // Executes in the block following `parent`, which is what a transaction sent now would see
+    // The randomness and the builder of the next block are unknown, so the parent's are reused
+    pub fn apply_next_header<TX>(&mut self, parent: &Block<TX>, block_time: Duration) {
+        self.apply_header(parent);
+
+        let block_env = &mut self.evm.env.block;
+        if let Some(number) = parent.number {
+            block_env.number = rU256::from(number.as_u64() + 1);
+        }
+        block_env.timestamp = (parent.timestamp + block_time.as_secs()).to_alloy();
+        if let Some(base_fee) = parent.base_fee_per_gas {
+            block_env.basefee =
+                next_base_fee(parent.gas_used, parent.gas_limit, base_fee).to_alloy();
+        }
+    }
+
+    // The current block env, in the format apply_block_overrides takes
+    pub fn current_block_overrides(&self) -> BlockOverrides {
+        let block_env = &self.evm.env.block;
+        BlockOverrides {
+            number: Some(U64::from(block_env.number.to::<u64>())),
+            difficulty: Some(block_env.difficulty.to_ethers()),
+            time: Some(U64::from(block_env.timestamp.to::<u64>())),
+            // memory simulators keep revm's unbounded default
+            gas_limit: Some(U64::from(block_env.gas_limit.saturating_to::<u64>())),
+            coinbase: Some(block_env.coinbase.to_ethers()),
+            random: block_env.prevrandao.map(|random| random.to_ethers()),
+            base_fee: Some(block_env.basefee.to_ethers()),
+        }
+    }
+
+    pub fn apply_block_overrides(&mut self, overrides: &BlockOverrides) {
+        let block_env = &mut self.evm.env.block;
+        if let Some(number) = overrides.number {
+            block_env.number = rU256::from(number.as_u64());
+        }
+        if let Some(difficulty) = overrides.difficulty {
+            block_env.difficulty = difficulty.to_alloy();
+        }
+        if let Some(time) = overrides.time {
+            block_env.timestamp = rU256::from(time.as_u64());
+        }
+        if let Some(gas_limit) = overrides.gas_limit {
+            block_env.gas_limit = rU256::from(gas_limit.as_u64());
+        }
+        if let Some(coinbase) = overrides.coinbase {
+            block_env.coinbase = coinbase.to_alloy();
+        }
+        if let Some(random) = overrides.random {
+            block_env.prevrandao = Some(random.to_alloy());
+        }
+        if let Some(base_fee) = overrides.base_fee {
+            block_env.basefee = base_fee.to_alloy();
+        }
+    }
+
+    // Fetches the header of the forked block and the chain id, and simulates in the block after it
+    pub async fn load_block_env(&mut self) -> Result<()> {
+        let parent = self
+            .provider
+            .get_block(self.block_number)
+            .await
+            .map_err(|e| anyhow!("Failed to fetch block {}: {e:?}", self.block_number))?
+            .ok_or(anyhow!("Block {} not found", self.block_number))?;
+        let chain_id = self
+            .provider
+            .get_chainid()
+            .await
+            .map_err(|e| anyhow!("Failed to fetch the chain id: {e:?}"))?;
+        let block_time = ChainConfig::from_chain_id(U64::from(chain_id.as_u64()))
+            .map(|chain| chain.block_time)
+            .unwrap_or(Duration::from_secs(12));
+
+        self.evm.env.cfg.chain_id = chain_id.as_u64();
+        self.apply_next_header(&parent, block_time);
+        self.apply_block_overrides(&self.block_overrides.clone());
+
+        Ok(())
+    }
+
+    // Base fee is disabled by default so calls with a zero gas price go through,
+    // enable it to charge the real basefee (and reject transactions that underpay it)
+    pub fn set_base_fee_enabled(&mut self, enabled: bool) {
+        self.evm.env.cfg.disable_base_fee = !enabled;
+    }
+
+    fn set_tx_env(&mut self, tx: &Transaction) {
         self.evm.env.tx.caller = tx.from.0.into();
-        self.evm.env.tx.transact_to = TransactTo::Call(tx.to.unwrap_or_default().0.into());
+        self.evm.env.tx.transact_to = match tx.to {
+            Some(to) => TransactTo::Call(to.0.into()),
+            None => TransactTo::Create(CreateScheme::Create),
+        };
         self.evm.env.tx.data = tx.input.0.clone().into();
         self.evm.env.tx.value = tx.value.to_alloy();
         self.evm.env.tx.chain_id = tx.chain_id.map(|id| id.as_u64());
         self.evm.env.tx.gas_limit = tx.gas.as_u64();
-
+        // Warm slots change the gas used, so they have to match the mined transaction
+        self.evm.env.tx.access_list = tx
+            .access_list
+            .as_ref()
+            .map(|access_list| {
+                access_list
+                    .0
+                    .iter()
+                    .map(|item| {
+                        let slots = item.storage_keys.iter().map(|key| rU256::from_be_bytes(key.0));
+                        (item.address.to_alloy(), slots.collect())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // The env is reused between replays, legacy transactions must not keep an earlier tip
         match tx.transaction_type {
-            Some(U64([0])) => {
-                self.evm.env.tx.gas_price = tx.gas_price.unwrap_or_default().to_alloy()
+            Some(U64([0])) | None => {
+                self.evm.env.tx.gas_priority_fee = None;
+                self.evm.env.tx.gas_price = tx.gas_price.unwrap_or_default().to_alloy();
             }
             Some(_) => {
                 self.evm.env.tx.gas_priority_fee =
                     tx.max_priority_fee_per_gas.map(|mpf| mpf.to_alloy());
                 self.evm.env.tx.gas_price = tx.max_fee_per_gas.unwrap_or_default().to_alloy();
             }
-            None => self.evm.env.tx.gas_price = tx.gas_price.unwrap_or_default().to_alloy(),
         }
+    }
 
-        let result = match self.evm.transact_commit() {
-            Ok(result) => result,
-            Err(e) => return Err(anyhow!("EVM call failed: {:?}", e)),
+    pub fn run_pending_tx(&mut self, tx: &Transaction) -> Result<TxResult> {
+        // We simply need to commit changes to the DB
+        self.set_tx_env(tx);
+
+        let (result, access) = if self.record_access {
+            let (result, access_list, state_diff) = self.transact_with_access(true)?;
+            (result, Some((access_list, state_diff)))
+        } else {
+            match self.evm.transact_commit() {
+                Ok(result) => (result, None),
+                Err(e) => return Err(anyhow!("EVM call failed: {:?}", e)),
+            }
         };
 
-        let output = match result {
+        let mut output = match result {
             ExecutionResult::Success { gas_used, gas_refunded, output, .. } => match output {
-                Output::Call(o) => TxResult { output: o.into(), gas_used, gas_refunded },
-                Output::Create(o, _) => TxResult { output: o.into(), gas_used, gas_refunded },
+                Output::Call(o) => TxResult {
+                    output: o.into(),
+                    gas_used,
+                    gas_refunded,
+                    access_list: None,
+                    state_diff: None,
+                },
+                Output::Create(o, _) => TxResult {
+                    output: o.into(),
+                    gas_used,
+                    gas_refunded,
+                    access_list: None,
+                    state_diff: None,
+                },
             },
             ExecutionResult::Revert { gas_used, output } => {
                 return Err(anyhow!("EVM REVERT: {:?} / Gas used: {:?}", output, gas_used))
             }
             ExecutionResult::Halt { reason, .. } => return Err(anyhow!("EVM HALT: {:?}", reason)),
         };
+        if let Some((access_list, state_diff)) = access {
+            output.access_list = Some(access_list);
+            output.state_diff = Some(state_diff);
+        }
 
         Ok(output)
     }
 
-    pub fn _call(&mut self, tx: Tx, commit: bool) -> Result<TxResult> {
+    // Same as run_pending_tx, but hands back the raw result so reverted txs keep their gas and logs
+    // Without commit the state changes are dropped, only the accounts it loaded stay cached
+    pub fn replay_tx(&mut self, tx: &Transaction, commit: bool) -> Result<ExecutionResult> {
+        self.set_tx_env(tx);
+        if commit {
+            self.evm.transact_commit().map_err(|e| anyhow!("EVM call failed: {:?}", e))
+        } else {
+            let ResultAndState { result, .. } =
+                self.evm.transact().map_err(|e| anyhow!("EVM call failed: {:?}", e))?;
+            Ok(result)
+        }
+    }
+
+    // Runs the transaction in the env with an access list inspector and diffs the resulting state
+    // against the DB before (optionally) committing it
+    fn transact_with_access(
+        &mut self,
+        commit: bool,
+    ) -> Result<(ExecutionResult, AccessList, StateDiff)> {
+        let mut inspector = AccessListInspector::default();
+        let ResultAndState { result, state } =
+            self.evm.inspect(&mut inspector).map_err(|e| anyhow!("EVM call failed: {:?}", e))?;
+
+        let db = self.evm.db.as_mut().unwrap();
+        let mut state_diff = StateDiff::new();
+        for (address, account) in &state {
+            let pre = db
+                .basic(*address)
+                .map_err(|e| anyhow!("Failed to load account {address:?}: {e:?}"))?
+                .unwrap_or_default();
+
+            let mut diff = AccountDiff::default();
+            if pre.balance != account.info.balance {
+                diff.balance = Some((pre.balance.to_ethers(), account.info.balance.to_ethers()));
+            }
+            if pre.nonce != account.info.nonce {
+                diff.nonce = Some((pre.nonce, account.info.nonce));
+            }
+            diff.code_changed = pre.code_hash != account.info.code_hash;
+            for (slot, value) in &account.storage {
+                if value.previous_or_original_value != value.present_value {
+                    diff.storage.insert(
+                        H256::from(slot.to_be_bytes::<32>()),
+                        (
+                            H256::from(value.previous_or_original_value.to_be_bytes::<32>()),
+                            H256::from(value.present_value.to_be_bytes::<32>()),
+                        ),
+                    );
+                }
+            }
+
+            if diff != AccountDiff::default() {
+                state_diff.insert(address.to_ethers(), diff);
+            }
+        }
+
+        if commit {
+            db.commit(state);
+        }
+
+        let mut excluded = vec![self.evm.env.tx.caller];
+        if let TransactTo::Call(to) = self.evm.env.tx.transact_to {
+            excluded.push(to);
+        }
+
+        Ok((result, inspector.access_list(&excluded), state_diff))
+    }
+
+    // Like _call, but hands back the raw result (logs included) instead of failing on reverts
+    pub fn call_raw(&mut self, tx: Tx, commit: bool) -> Result<ExecutionResult> {
+        self.set_call_env(tx);
+        if commit {
+            self.evm.transact_commit().map_err(|e| anyhow!("EVM call failed: {:?}", e))
+        } else {
+            let ref_tx =
+                self.evm.transact_ref().map_err(|e| anyhow!("EVM staticcall failed: {:?}", e))?;
+            Ok(ref_tx.result)
+        }
+    }
+
+    // Executes the call with the call tracer attached, the tree is returned whether it succeeds or not
+    pub fn trace_call(&mut self, tx: Tx, commit: bool) -> Result<CallFrame> {
+        self.set_call_env(tx);
+        self.inspect_call_tree(commit)
+    }
+
+    pub fn trace_tx(&mut self, tx: &Transaction) -> Result<CallFrame> {
+        self.set_tx_env(tx);
+        self.inspect_call_tree(true)
+    }
+
+    fn inspect_call_tree(&mut self, commit: bool) -> Result<CallFrame> {
+        let mut tracer = CallTracer::default();
+        let ResultAndState { state, .. } =
+            self.evm.inspect(&mut tracer).map_err(|e| anyhow!("EVM call failed: {:?}", e))?;
+        if commit {
+            self.evm.db.as_mut().unwrap().commit(state);
+        }
+        tracer.root.ok_or(anyhow!("Call tracer recorded no frames"))
+    }
+
+    fn set_call_env(&mut self, tx: Tx) {
         self.evm.env.tx.caller = tx.caller.to_alloy();
         self.evm.env.tx.transact_to = TransactTo::Call(tx.transact_to.to_alloy());
         self.evm.env.tx.data = tx.data.into();
         self.evm.env.tx.value = tx.value.to_alloy();
-        self.evm.env.tx.gas_limit = 5000000;
+        self.evm.env.tx.gas_limit = tx.gas_limit;
+        self.evm.env.tx.gas_price = tx.gas_price.to_alloy();
+        self.evm.env.tx.gas_priority_fee = tx.gas_priority_fee.map(|fee| fee.to_alloy());
+    }
+
+    pub fn _call(&mut self, tx: Tx, commit: bool) -> Result<TxResult> {
+        self.set_call_env(tx);
 
-        let result = if commit {
+        let (result, access) = if self.record_access {
+            let (result, access_list, state_diff) = self.transact_with_access(commit)?;
+            (result, Some((access_list, state_diff)))
+        } else if commit {
             match self.evm.transact_commit() {
-                Ok(result) => result,
+                Ok(result) => (result, None),
                 Err(e) => return Err(anyhow!("EVM call failed: {:?}", e)),
             }
         } else {
             let ref_tx =
                 self.evm.transact_ref().map_err(|e| anyhow!("EVM staticcall failed: {:?}", e))?;
-            ref_tx.result
+            (ref_tx.result, None)
         };
 
-        let output = match result {
+        let mut output = match result {
             ExecutionResult::Success { gas_used, gas_refunded, output, .. } => match output {
-                Output::Call(o) => TxResult { output: o.into(), gas_used, gas_refunded },
-                Output::Create(o, _) => TxResult { output: o.into(), gas_used, gas_refunded },
+                Output::Call(o) => TxResult {
+                    output: o.into(),
+                    gas_used,
+                    gas_refunded,
+                    access_list: None,
+                    state_diff: None,
+                },
+                Output::Create(o, _) => TxResult {
+                    output: o.into(),
+                    gas_used,
+                    gas_refunded,
+                    access_list: None,
+                    state_diff: None,
+                },
             },
             ExecutionResult::Revert { gas_used, output } => {
                 return Err(anyhow!("EVM REVERT: {:?} / Gas used: {:?}", output, gas_used))
             }
             ExecutionResult::Halt { reason, .. } => return Err(anyhow!("EVM HALT: {:?}", reason)),
         };
+        if let Some((access_list, state_diff)) = access {
+            output.access_list = Some(access_list);
+            output.state_diff = Some(state_diff);
+        }
 
         Ok(output)
     }
@@ -199,6 +855,99 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
         self._call(tx, true)
     }
 
+    pub fn apply_state_override(&mut self, overrides: &StateOverride) -> Result<()> {
+        let db = self.evm.db.as_mut().unwrap();
+        for (address, account) in overrides {
+            let address = address.to_alloy();
+            let mut info = db
+                .basic(address)
+                .map_err(|e| anyhow!("Failed to load account {address:?}: {e:?}"))?
+                .unwrap_or_default();
+            if let Some(balance) = account.balance {
+                info.balance = balance.to_alloy();
+            }
+            if let Some(nonce) = account.nonce {
+                info.nonce = nonce.as_u64();
+            }
+            if let Some(code) = &account.code {
+                let code = Bytecode::new_raw(code.0.clone().into());
+                let code_hash = B256::from_slice(&keccak256(code.bytes())[..]);
+                info = AccountInfo::new(info.balance, info.nonce, code_hash, code);
+            }
+            db.insert_account_info(address, info);
+
+            if let Some(state) = &account.state {
+                let storage = state
+                    .iter()
+                    .map(|(slot, value)| {
+                        (rU256::from_be_bytes(slot.0), rU256::from_be_bytes(value.0))
+                    })
+                    .collect();
+                db.replace_account_storage(address, storage)?;
+            }
+            if let Some(state_diff) = &account.state_diff {
+                for (slot, value) in state_diff {
+                    db.insert_account_storage(
+                        address,
+                        rU256::from_be_bytes(slot.0),
+                        rU256::from_be_bytes(value.0),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Runs `f` with the overrides applied and restores the previous state afterwards,
+    // along with everything `f` committed
+    pub fn with_state_override<T>(
+        &mut self,
+        overrides: &StateOverride,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let snapshot = self.evm.db.clone().unwrap();
+        let result = self.apply_state_override(overrides).and_then(|_| f(self));
+        self.inject_db(snapshot);
+        result
+    }
+
+    // Binary searches the smallest gas limit the call succeeds with, like eth_estimateGas
+    // tx.gas_limit is the upper bound, the state is never committed
+    pub fn estimate_gas(&mut self, tx: Tx) -> Result<u64> {
+        let cap = tx.gas_limit;
+        let result = self
+            .staticcall(tx.clone())
+            .map_err(|e| anyhow!("Call fails with the gas cap of {cap}: {e:?}"))?;
+
+        // Refunds and the 63/64 rule make the needed limit higher than gas_used, never lower
+        let mut lo = result.gas_used.max(TX_BASE_GAS) - 1;
+        let mut hi = cap;
+        while lo + 1 < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.staticcall(Tx { gas_limit: mid, ..tx.clone() }) {
+                Ok(_) => hi = mid,
+                Err(_) => lo = mid,
+            }
+        }
+
+        Ok(hi)
+    }
+
+    // Price paid per gas unit in the simulated block: max fee capped at basefee + priority fee
+    pub fn effective_gas_price(&self, tx: &Tx) -> U256 {
+        match tx.gas_priority_fee {
+            Some(priority_fee) => {
+                let base_fee = self.evm.env.block.basefee.to_ethers();
+                tx.gas_price.min(base_fee + priority_fee)
+            }
+            None => tx.gas_price,
+        }
+    }
+
+    pub fn gas_cost(&self, tx: &Tx, gas_used: u64) -> U256 {
+        self.effective_gas_price(tx) * U256::from(gas_used)
+    }
+
     pub async fn execute_set_token_balance(
         &mut self,
         token: H160,
@@ -223,10 +972,9 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
         Ok(())
     }
 
-    // Simulate a transfer and return the tax rate
-    pub async fn simulate_simple_transfer(&mut self, token: H160) -> Result<U256> {
-        let amount_u32 = 10000;
-        let token_info = get_token_info(self.provider.clone(), token).await?;
+    // Simulate a transfer of `amount_u32` whole tokens and return the tax in basis points
+    pub async fn simulate_simple_transfer(&mut self, token: H160, amount_u32: u32) -> Result<u64> {
+        let token_info = get_token_info(self.provider.clone(), token, None).await?;
         let amount = U256::from(amount_u32)
             .checked_mul(U256::from(10).pow(U256::from(token_info.decimals)))
             .ok_or(anyhow!("Overflow occured while calculating amount"))?;
@@ -249,19 +997,14 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
         // TODO: Make a validation against gas cost
         // let gas_cost = out.1;
 
-        // Calculate the tax rate of the transfer
+        // Calculate the tax of the transfer in basis points
         let sent_amount = transfer_result.transfered_amount;
         let reducted_out_amount = amount
             .checked_sub(sent_amount)
             .ok_or(anyhow!("Overflow occured while calculating reducted out amount"))?;
-        let transfer_tax_rate = reducted_out_amount
-            .checked_mul(U256::from(100))
-            .unwrap()
-            .checked_div(amount)
-            .ok_or(anyhow!("Overflow occured while calculating tax rate"))?;
 
         // NOTE: should we return gas comsumption?
-        Ok(transfer_tax_rate)
+        Ok(bps(reducted_out_amount, amount))
     }
 
     pub fn get_eth_balance(&mut self) -> U256 {
@@ -270,10 +1013,12 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
     }
 
     pub fn set_eth_balance(&mut self, balance: u32) {
-        let user_balance =
-            rU256::from(balance).checked_mul(rU256::from(10).pow(rU256::from(18))).unwrap();
-        let user_info = AccountInfo::new(user_balance, 0, KECCAK_EMPTY, Bytecode::default());
-        self.evm.db.as_mut().unwrap().insert_account_info(self.owner.to_alloy(), user_info);
+        let user_balance = U256::from(balance) * *WEI;
+        let overrides = StateOverride::from([(
+            self.owner,
+            AccountOverride { balance: Some(user_balance), ..Default::default() },
+        )]);
+        self.apply_state_override(&overrides).unwrap();
     }
 
     // ERC-20 Token functions
@@ -289,16 +1034,20 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             abi::Token::Address(account),
             abi::Token::Uint(U256::from(slot)),
         ]));
-        let target_balance = rU256::from(balance)
-            .checked_mul(rU256::from(10).pow(rU256::from(decimals)))
+        let target_balance = U256::from(balance)
+            .checked_mul(U256::from(10).pow(U256::from(decimals)))
             .ok_or(anyhow!("Overflow occured while calculating balance"))?;
-        self.evm.db.as_mut().unwrap().insert_account_storage(
-            token.to_alloy(),
-            slot.into(),
-            target_balance,
-        )?;
-
-        Ok(())
+        let overrides = StateOverride::from([(
+            token,
+            AccountOverride {
+                state_diff: Some(HashMap::from([(
+                    H256::from(slot.0),
+                    H256::from(target_balance.to_alloy().to_be_bytes::<32>()),
+                )])),
+                ..Default::default()
+            },
+        )]);
+        self.apply_state_override(&overrides)
     }
 
     pub fn token_balance_of(&mut self, token: H160, account: H160) -> Result<U256> {
@@ -308,21 +1057,572 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             transact_to: token,
             data: calldata.0,
             value: U256::zero(),
-            gas_limit: 0,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
         })?;
         let out = self.token.balance_of_output(value.output)?;
         Ok(out)
     }
 
-    // V2 Pool functions
-    pub fn set_v2_pool_reserves(&mut self, pool: H160, reserves: rU256) {
-        let slot = rU256::from(8);
-        self.evm
+    pub fn token_decimals(&mut self, token: H160) -> Result<u8> {
+        let calldata = self.token.decimals_input()?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: token,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let out = self.token.decimals_output(value.output)?;
+        Ok(out)
+    }
+
+    pub fn token_allowance(&mut self, token: H160, owner: H160, spender: H160) -> Result<U256> {
+        let calldata = self.token.allowance_input(owner, spender)?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: token,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let out = self.token.allowance_output(value.output)?;
+        Ok(out)
+    }
+
+    pub fn token_total_supply(&mut self, token: H160) -> Result<U256> {
+        let calldata = self.token.total_supply_input()?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: token,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let out = self.token.total_supply_output(value.output)?;
+        Ok(out)
+    }
+
+    pub fn get_storage(&mut self, address: Address, slot: rU256) -> Result<rU256> {
+        let value = self
+            .evm
             .db
             .as_mut()
             .unwrap()
-            .insert_account_storage(pool.to_alloy(), slot, reserves)
-            .unwrap();
+            .storage(address, slot)
+            .map_err(|e| anyhow!("Failed to load slot {slot} of {address:?}: {e:?}"))?;
+        Ok(value)
+    }
+
+    pub fn get_code(&mut self, address: H160) -> Result<ethers::types::Bytes> {
+        let db = self.evm.db.as_mut().unwrap();
+        let info = db
+            .basic(address.to_alloy())
+            .map_err(|e| anyhow!("Failed to load account {address:?}: {e:?}"))?
+            .unwrap_or_default();
+        let code = match info.code {
+            Some(code) => code,
+            None => db
+                .code_by_hash(info.code_hash)
+                .map_err(|e| anyhow!("Failed to load the code of {address:?}: {e:?}"))?,
+        };
+        Ok(code.original_bytes().to_vec().into())
+    }
+
+    pub fn set_storage(&mut self, address: Address, slot: rU256, value: rU256) -> Result<()> {
+        self.evm.db.as_mut().unwrap().insert_account_storage(address, slot, value)?;
+        Ok(())
+    }
+
+    // Runs a call without committing it and returns the storage slots it read, in order
+    pub fn record_sloads(&mut self, to: H160, data: Bytes) -> Result<Vec<(Address, rU256)>> {
+        self.set_call_env(Tx {
+            caller: self.owner,
+            transact_to: to,
+            data,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        });
+
+        let mut recorder = StorageRecorder::default();
+        self.evm.inspect(&mut recorder).map_err(|e| anyhow!("EVM call failed: {:?}", e))?;
+        Ok(recorder.reads)
+    }
+
+    // Sets the token balance of `account` to exactly `amount`, whatever the storage layout:
+    // the slot is found from the SLOADs of balanceOf, the write is verified through balanceOf,
+    // and tokens whose storage can't be patched get the amount transferred from a holder instead
+    pub async fn deal(
+        &mut self,
+        token: H160,
+        account: H160,
+        amount: U256,
+        adjust_total_supply: bool,
+    ) -> Result<()> {
+        let before = self.token_balance_of(token, account)?;
+
+        match self.deal_storage(token, account, amount) {
+            Ok(()) => {
+                if adjust_total_supply {
+                    self.adjust_total_supply(token, before, amount)?;
+                }
+                return Ok(());
+            }
+            Err(e) => debug!("Writing the balance of {token:?} failed, using a holder: {e:?}"),
+        }
+
+        self.deal_from_holder(token, account, amount).await
+    }
+
+    fn deal_storage(&mut self, token: H160, account: H160, amount: U256) -> Result<()> {
+        let calldata = self.token.balance_of_input(account)?;
+        let reads = self.record_sloads(token, calldata.0)?;
+        let target = amount.to_alloy();
+
+        // Getters usually read the balance last, after paused flags, proxies and share ratios
+        let mut tried = HashSet::new();
+        for (address, slot) in reads.into_iter().rev() {
+            if !tried.insert((address, slot)) {
+                continue;
+            }
+            let original = self.get_storage(address, slot)?;
+
+            // Plain uint256 balance (Solidity mappings, Vyper HashMaps)
+            self.set_storage(address, slot, target)?;
+            match self.token_balance_of(token, account) {
+                Ok(balance) if balance == amount => return Ok(()),
+                // Share based tokens (stETH) scale the raw value, solve for the shares
+                Ok(balance) if !balance.is_zero() && balance != amount => {
+                    if let Ok(shares) =
+                        U256::try_from(amount.full_mul(amount) / U512::from(balance))
+                    {
+                        self.set_storage(address, slot, shares.to_alloy())?;
+                        if let Ok(balance) = self.token_balance_of(token, account) {
+                            let diff =
+                                if balance > amount { balance - amount } else { amount - balance };
+                            if diff <= U256::one() {
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            // Balance packed with other fields in the low bits of the slot
+            for bits in [128usize, 96] {
+                let mask = (rU256::from(1) << bits) - rU256::from(1);
+                if target > mask {
+                    continue;
+                }
+                self.set_storage(address, slot, (original & !mask) | target)?;
+                if matches!(self.token_balance_of(token, account), Ok(balance) if balance == amount)
+                {
+                    return Ok(());
+                }
+            }
+
+            self.set_storage(address, slot, original)?;
+        }
+
+        Err(anyhow!("No storage slot of {token:?} holds the balance of {account:?}"))
+    }
+
+    fn adjust_total_supply(&mut self, token: H160, before: U256, after: U256) -> Result<()> {
+        let supply = self.token_total_supply(token)?;
+        let target = if after >= before {
+            supply.saturating_add(after - before)
+        } else {
+            supply.saturating_sub(before - after)
+        };
+
+        let calldata = self.token.total_supply_input()?;
+        let reads = self.record_sloads(token, calldata.0)?;
+        for (address, slot) in reads.into_iter().rev() {
+            let original = self.get_storage(address, slot)?;
+            if original != supply.to_alloy() {
+                continue;
+            }
+            self.set_storage(address, slot, target.to_alloy())?;
+            if matches!(self.token_total_supply(token), Ok(total) if total == target) {
+                return Ok(());
+            }
+            self.set_storage(address, slot, original)?;
+        }
+
+        Err(anyhow!("No storage slot of {token:?} holds the total supply"))
+    }
+
+    // Moves the difference between `account` and a large holder found in the recent Transfer logs
+    async fn deal_from_holder(&mut self, token: H160, account: H160, amount: U256) -> Result<()> {
+        let current = self.token_balance_of(token, account)?;
+        if current == amount {
+            return Ok(());
+        }
+
+        let filter = Filter::new()
+            .address(token)
+            .topic0(*TRANSFER_TOPIC)
+            .from_block(self.block_number.saturating_sub(HOLDER_LOOKBACK_BLOCKS.into()))
+            .to_block(self.block_number);
+        let logs = self
+            .provider
+            .get_logs(&filter)
+            .await
+            .map_err(|e| anyhow!("Failed to fetch the Transfer logs of {token:?}: {e:?}"))?;
+        let recipients: BTreeSet<H160> = logs
+            .iter()
+            .filter_map(|log| log.topics.get(2))
+            .map(|topic| H160::from_slice(&topic.as_bytes()[12..]))
+            .filter(|holder| *holder != account && !holder.is_zero())
+            .collect();
+
+        let mut holder: Option<(H160, U256)> = None;
+        for recipient in recipients {
+            let balance = self.token_balance_of(token, recipient).unwrap_or_default();
+            if holder.map(|(_, best)| balance > best).unwrap_or(true) {
+                holder = Some((recipient, balance));
+            }
+        }
+        let (holder, holder_balance) =
+            holder.ok_or(anyhow!("No holder of {token:?} found in the recent Transfer logs"))?;
+
+        let (from, to, delta) = if amount > current {
+            (holder, account, amount - current)
+        } else {
+            (account, holder, current - amount)
+        };
+        if from == holder && holder_balance < delta {
+            return Err(anyhow!(
+                "Largest holder {holder:?} of {token:?} only has {holder_balance}, {delta} needed"
+            ));
+        }
+
+        // Transfer output is not decoded, tokens without a return value are fine here
+        let calldata = self.token.transfer_input(to, delta)?;
+        self.call(Tx {
+            caller: from,
+            transact_to: token,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+
+        let balance = self.token_balance_of(token, account)?;
+        if balance != amount {
+            return Err(anyhow!(
+                "Balance of {account:?} is {balance} after dealing {amount} {token:?}, taxed transfer"
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Transfers `amount` from the owner to `recipient` and follows where every token went:
+    // the balances of both parties, the token contract, the zero/dead addresses and
+    // any other account credited in the Transfer logs, plus the total supply
+    pub async fn analyze_transfer(
+        &mut self,
+        token: H160,
+        recipient: H160,
+        amount: U256,
+    ) -> Result<TransferAnalysis> {
+        let sender = self.owner;
+        self.deal(token, sender, amount, false).await?;
+
+        let calldata = self.token.transfer_input(recipient, amount)?;
+        let tx = Tx {
+            caller: sender,
+            transact_to: token,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        };
+
+        // Dry run first to learn the fee recipients from the logs before taking balances
+        let logs = match self.call_raw(tx.clone(), false)? {
+            ExecutionResult::Success { logs, .. } => logs,
+            ExecutionResult::Revert { output, .. } => {
+                return Err(anyhow!("Transfer of {token:?} reverted: {output:?}"))
+            }
+            ExecutionResult::Halt { reason, .. } => {
+                return Err(anyhow!("Transfer of {token:?} halted: {reason:?}"))
+            }
+        };
+        let mut accounts = vec![sender, recipient, token, *ZERO_ADDRESS, *DEAD_ADDRESS];
+        let (_, transfers, _) = decode_effects(&logs);
+        for transfer in transfers.iter().filter(|transfer| transfer.token == token) {
+            if !accounts.contains(&transfer.to) {
+                accounts.push(transfer.to);
+            }
+        }
+
+        let mut before = Vec::new();
+        for account in &accounts {
+            before.push(self.token_balance_of(token, *account)?);
+        }
+        let supply_before = self.token_total_supply(token).unwrap_or_default();
+
+        let gas_used = match self.call_raw(tx, true)? {
+            ExecutionResult::Success { gas_used, .. } => gas_used,
+            result => return Err(anyhow!("Transfer of {token:?} failed: {result:?}")),
+        };
+
+        let mut deltas = Vec::new();
+        for (account, before) in accounts.into_iter().zip(before) {
+            let after = self.token_balance_of(token, account)?;
+            deltas.push(BalanceDelta { account, before, after });
+        }
+        let others = deltas.split_off(5);
+        let [sender, recipient, contract, zero, dead]: [BalanceDelta; 5] =
+            deltas.try_into().map_err(|_| anyhow!("Missing tracked balances of {token:?}"))?;
+        let balances = TrackedBalances { sender, recipient, contract, zero, dead, others };
+        let supply_after = self.token_total_supply(token).unwrap_or_default();
+
+        Ok(TransferAnalysis::new(token, amount, balances, (supply_before, supply_after), gas_used))
+    }
+
+    // Runs the ERC-20 compliance suite on a copy of the state, nothing it does is kept
+    // `amount` is dealt to the owner and every check moves a quarter of it
+    pub async fn check_erc20_compliance(
+        &mut self,
+        token: H160,
+        amount: U256,
+    ) -> Result<ComplianceReport> {
+        let snapshot = self.evm.db.clone().unwrap();
+        let result = self.run_compliance_checks(token, amount).await;
+        self.inject_db(snapshot);
+        result
+    }
+
+    async fn run_compliance_checks(
+        &mut self,
+        token: H160,
+        amount: U256,
+    ) -> Result<ComplianceReport> {
+        let owner = self.owner;
+        let recipient = *TRANSFER_RECIPIENT;
+        let spender = *APPROVED_SPENDER;
+        self.deal(token, owner, amount, false).await?;
+
+        let part = amount / 4;
+        let mut checks = Vec::new();
+
+        // transfer
+        let before = self.token_balance_of(token, recipient)?;
+        let calldata = self.token.transfer_input(recipient, part)?;
+        checks.push(match self.compliance_call(owner, token, "transfer", calldata) {
+            Err(reason) => CheckVerdict::fail(ComplianceCheck::Transfer, reason),
+            Ok(()) if self.token_balance_of(token, recipient)? <= before => {
+                CheckVerdict::fail(ComplianceCheck::Transfer, "recipient was not credited")
+            }
+            Ok(()) => CheckVerdict::pass(ComplianceCheck::Transfer),
+        });
+
+        // approve + transferFrom, approving more than is moved so that the decrement shows
+        let approved = part * 2;
+        let before = self.token_balance_of(token, recipient)?;
+        let calldata = self.token.approve_input(spender, approved)?;
+        match self.compliance_call(owner, token, "approve", calldata) {
+            Err(reason) => {
+                checks.push(CheckVerdict::fail(
+                    ComplianceCheck::ApproveTransferFrom,
+                    format!("approve failed: {reason}"),
+                ));
+                checks
+                    .push(CheckVerdict::fail(ComplianceCheck::AllowanceDecrement, "no allowance"));
+            }
+            Ok(()) => {
+                let calldata = self.token.transfer_from_input(owner, recipient, part)?;
+                match self.compliance_call(spender, token, "transferFrom", calldata) {
+                    Err(reason) => {
+                        checks.push(CheckVerdict::fail(
+                            ComplianceCheck::ApproveTransferFrom,
+                            format!("transferFrom failed: {reason}"),
+                        ));
+                        checks.push(CheckVerdict::fail(
+                            ComplianceCheck::AllowanceDecrement,
+                            "nothing was moved",
+                        ));
+                    }
+                    Ok(()) => {
+                        checks.push(if self.token_balance_of(token, recipient)? <= before {
+                            CheckVerdict::fail(
+                                ComplianceCheck::ApproveTransferFrom,
+                                "recipient was not credited",
+                            )
+                        } else {
+                            CheckVerdict::pass(ComplianceCheck::ApproveTransferFrom)
+                        });
+
+                        let allowance = self.token_allowance(token, owner, spender)?;
+                        let expected = approved - part;
+                        checks.push(if allowance == expected {
+                            CheckVerdict::pass(ComplianceCheck::AllowanceDecrement)
+                        } else {
+                            CheckVerdict::fail(
+                                ComplianceCheck::AllowanceDecrement,
+                                format!("allowance is {allowance}, expected {expected}"),
+                            )
+                        });
+                    }
+                }
+            }
+        }
+
+        // zero amount transfer
+        let calldata = self.token.transfer_input(recipient, U256::zero())?;
+        checks.push(match self.compliance_call(owner, token, "transfer", calldata) {
+            Err(reason) => CheckVerdict::fail(ComplianceCheck::ZeroAmountTransfer, reason),
+            Ok(()) => CheckVerdict::pass(ComplianceCheck::ZeroAmountTransfer),
+        });
+
+        // self transfer, taxes may still take a cut but the balance must never grow
+        let before = self.token_balance_of(token, owner)?;
+        let calldata = self.token.transfer_input(owner, part)?;
+        checks.push(match self.compliance_call(owner, token, "transfer", calldata) {
+            Err(reason) => CheckVerdict::fail(ComplianceCheck::SelfTransfer, reason),
+            Ok(()) => {
+                let after = self.token_balance_of(token, owner)?;
+                if after > before {
+                    CheckVerdict::fail(
+                        ComplianceCheck::SelfTransfer,
+                        format!("balance grew from {before} to {after}"),
+                    )
+                } else {
+                    CheckVerdict::pass(ComplianceCheck::SelfTransfer)
+                }
+            }
+        });
+
+        Ok(ComplianceReport { token, amount, checks })
+    }
+
+    // A committed token call of the compliance suite, the error is the reason it failed
+    fn compliance_call(
+        &mut self,
+        caller: H160,
+        token: H160,
+        function: &str,
+        data: ethers::types::Bytes,
+    ) -> std::result::Result<(), String> {
+        match self.token_call(caller, token, function, data, true) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("returned false".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    // V2 Pool functions
+    // Writes the reserves into the pair's storage, keeping blockTimestampLast, and deals the pair
+    // the same token balances so swap's balance/K checks hold against the new reserves
+    pub async fn set_v2_reserves(
+        &mut self,
+        pool: H160,
+        reserve0: u128,
+        reserve1: u128,
+    ) -> Result<()> {
+        let max_reserve = (1u128 << 112) - 1;
+        if reserve0 > max_reserve || reserve1 > max_reserve {
+            return Err(anyhow!("Reserves of {pool:?} must fit in uint112"));
+        }
+
+        let (current0, current1, block_timestamp_last) = self.v2_pool_get_reserves(pool)?;
+        let calldata = self.v2_pool.get_reserves_input()?;
+        let reads: Vec<rU256> = self
+            .record_sloads(pool, calldata.0)?
+            .into_iter()
+            .filter(|(address, _)| *address == pool.to_alloy())
+            .map(|(_, slot)| slot)
+            .collect();
+
+        // Uniswap V2 packs both reserves and the timestamp in one slot, some forks use one slot each
+        let packed = pack_v2_reserves(current0, current1, block_timestamp_last);
+        let mut packed_slot = None;
+        for slot in &reads {
+            if self.get_storage(pool.to_alloy(), *slot)? == packed {
+                packed_slot = Some(*slot);
+                break;
+            }
+        }
+
+        match packed_slot {
+            Some(slot) => self.set_storage(
+                pool.to_alloy(),
+                slot,
+                pack_v2_reserves(reserve0, reserve1, block_timestamp_last),
+            )?,
+            None => {
+                let mut slot0 = None;
+                let mut slot1 = None;
+                for slot in &reads {
+                    let value = self.get_storage(pool.to_alloy(), *slot)?;
+                    if slot0.is_none() && value == rU256::from(current0) {
+                        slot0 = Some(*slot);
+                    } else if slot1.is_none() && value == rU256::from(current1) {
+                        slot1 = Some(*slot);
+                    }
+                }
+                let (slot0, slot1) =
+                    slot0.zip(slot1).ok_or(anyhow!("Reserves slot of {pool:?} not found"))?;
+                self.set_storage(pool.to_alloy(), slot0, rU256::from(reserve0))?;
+                self.set_storage(pool.to_alloy(), slot1, rU256::from(reserve1))?;
+            }
+        }
+
+        let (new0, new1, _) = self.v2_pool_get_reserves(pool)?;
+        if (new0, new1) != (reserve0, reserve1) {
+            return Err(anyhow!(
+                "getReserves of {pool:?} returns ({new0}, {new1}) after the write"
+            ));
+        }
+
+        let (token0, token1) = self.v2_pool_tokens(pool)?;
+        self.deal(token0, pool, U256::from(reserve0), false).await?;
+        self.deal(token1, pool, U256::from(reserve1), false).await?;
+
+        Ok(())
+    }
+
+    pub fn v2_pool_tokens(&mut self, pool: H160) -> Result<(H160, H160)> {
+        let calldata = self.v2_pool.token0_input()?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: pool,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let token0 = self.v2_pool.token0_output(value.output)?;
+
+        let calldata = self.v2_pool.token1_input()?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: pool,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let token1 = self.v2_pool.token1_output(value.output)?;
+
+        Ok((token0, token1))
     }
 
     pub fn v2_pool_get_reserves(&mut self, pool: H160) -> Result<(u128, u128, u32)> {
@@ -332,12 +1632,181 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             transact_to: pool,
             data: calldata.0,
             value: U256::zero(),
-            gas_limit: 0,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
         })?;
         let out = self.v2_pool.get_reserves_output(value.output)?;
         Ok(out)
     }
 
+    pub fn v2_pool_factory(&mut self, pool: H160) -> Result<H160> {
+        let calldata = self.v2_pool.factory_input()?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: pool,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let out = self.v2_pool.factory_output(value.output)?;
+        Ok(out)
+    }
+
+    pub fn v2_factory_get_pair(
+        &mut self,
+        factory: H160,
+        token_a: H160,
+        token_b: H160,
+    ) -> Result<H160> {
+        let calldata = self.v2_factory.get_pair_input(token_a, token_b)?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: factory,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let out = self.v2_factory.get_pair_output(value.output)?;
+        Ok(out)
+    }
+
+    // Router functions
+    pub fn v2_router_factory(&mut self, router: H160) -> Result<H160> {
+        let calldata = self.v2_router.factory_input()?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: router,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let out = self.v2_router.factory_output(value.output)?;
+        Ok(out)
+    }
+
+    pub fn v2_router_get_amounts_out(
+        &mut self,
+        router: H160,
+        amount_in: U256,
+        path: Vec<H160>,
+    ) -> Result<Vec<U256>> {
+        let calldata = self.v2_router.get_amounts_out_input(amount_in, path)?;
+        let value = self.staticcall(Tx {
+            caller: self.owner,
+            transact_to: router,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })?;
+        let out = self.v2_router.get_amounts_out_output(value.output)?;
+        Ok(out)
+    }
+
+    // Buys the token with native ETH from the owner through the router
+    // Returns (amount quoted by the router, amount actually received by the owner)
+    pub fn v2_router_buy(
+        &mut self,
+        router: H160,
+        weth: H160,
+        token: H160,
+        amount_in: U256,
+    ) -> Result<(U256, U256)> {
+        let path = vec![weth, token];
+        let quoted = self.v2_router_get_amounts_out(router, amount_in, path.clone())?;
+        let expected = *quoted.last().ok_or(anyhow!("Router returned an empty quote"))?;
+
+        let balance_before = self.token_balance_of(token, self.owner)?;
+        let calldata = self.v2_router.swap_exact_eth_for_tokens_input(
+            U256::zero(),
+            path,
+            self.owner,
+            U256::MAX,
+        )?;
+        self.call(Tx {
+            caller: self.owner,
+            transact_to: router,
+            data: calldata.0,
+            value: amount_in,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })
+        .map_err(SwapError::TxFailed)?;
+        let balance_after = self.token_balance_of(token, self.owner)?;
+
+        Ok((expected, balance_after.saturating_sub(balance_before)))
+    }
+
+    // Sells the owner's tokens back to native ETH through the router
+    // Returns (amount quoted by the router, ETH actually received by the owner)
+    pub fn v2_router_sell(
+        &mut self,
+        router: H160,
+        weth: H160,
+        token: H160,
+        amount_in: U256,
+    ) -> Result<(U256, U256)> {
+        let path = vec![token, weth];
+        let quoted = self.v2_router_get_amounts_out(router, amount_in, path.clone())?;
+        let expected = *quoted.last().ok_or(anyhow!("Router returned an empty quote"))?;
+
+        // a token refusing the approval is as good as a failed sell
+        if !self.approve(token, router, true).map_err(SwapError::TxFailed)? {
+            return Err(SwapError::TxFailed(anyhow!("approve returned false")).into());
+        }
+
+        // gas is free in the simulator, so the balance delta is exactly what the router paid out
+        let balance_before = self.get_eth_balance();
+        let calldata = self.v2_router.swap_exact_tokens_for_eth_input(
+            amount_in,
+            U256::zero(),
+            path,
+            self.owner,
+            U256::MAX,
+        )?;
+        self.call(Tx {
+            caller: self.owner,
+            transact_to: router,
+            data: calldata.0,
+            value: U256::zero(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        })
+        .map_err(SwapError::TxFailed)?;
+        let balance_after = self.get_eth_balance();
+
+        Ok((expected, balance_after.saturating_sub(balance_before)))
+    }
+
+    // Deploys init code (constructor arguments appended) from the owner and returns its address
+    pub fn deploy(&mut self, init_code: Bytes) -> Result<H160> {
+        self.evm.env.tx.caller = self.owner.to_alloy();
+        self.evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        self.evm.env.tx.data = init_code.into();
+        self.evm.env.tx.value = rU256::ZERO;
+        self.evm.env.tx.gas_limit = DEFAULT_GAS_LIMIT;
+        self.evm.env.tx.gas_price = rU256::ZERO;
+        self.evm.env.tx.gas_priority_fee = None;
+
+        match self.evm.transact_commit() {
+            Ok(ExecutionResult::Success { output: Output::Create(_, Some(address)), .. }) => {
+                Ok(address.to_ethers())
+            }
+            Ok(result) => Err(anyhow!("Deployment failed: {:?}", result)),
+            Err(e) => Err(anyhow!("EVM call failed: {:?}", e)),
+        }
+    }
+
     // Simulator functions
     pub fn deploy_simulator(&mut self) {
         let code = Bytecode::new_raw((*SIMULATOR_CODE.0).into());
@@ -369,7 +1838,9 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             transact_to: self.simulator_address,
             data: calldata.0,
             value: U256::zero(),
-            gas_limit: 5000000,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
         };
         let value = if commit {
             match self.call(tx) {
@@ -398,7 +1869,9 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             transact_to: self.simulator_address,
             data: calldata.0,
             value: U256::zero(),
-            gas_limit: 5000000,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
         })?;
         let out = self.simulator.get_amount_out_output(value.output)?;
         Ok(out)
@@ -418,23 +1891,10 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
     }
 
     pub fn approve(&mut self, token: H160, spender: H160, commit: bool) -> Result<bool> {
-        let calldata = self.token.approve_input(spender)?;
-
-        let tx = Tx {
-            caller: self.owner,
-            transact_to: token,
-            data: calldata.0,
-            value: U256::zero(),
-            gas_limit: 5000000,
-        };
-
-        let value = if commit { self.call(tx)? } else { self.staticcall(tx)? };
-        let out = self.token.approve_output(value.output)?;
-        Ok(out)
+        let calldata = self.token.approve_input(spender, U256::MAX)?;
+        self.token_call(self.owner, token, "approve", calldata, commit)
     }
 
-    // This function will fail with the message of
-    // "missing or wrong function selector"
     pub fn transfer(
         &mut self,
         token: H160,
@@ -443,18 +1903,56 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
         commit: bool,
     ) -> Result<bool> {
         let calldata = self.token.transfer_input(recipient, amount)?;
+        self.token_call(self.owner, token, "transfer", calldata, commit)
+    }
 
+    // Calls a bool-returning ERC-20 function (transfer, transferFrom, approve) the way SafeERC20 does
+    // Reverts and calls to addresses without code fail with a TokenCallError, missing return
+    // data counts as success and an explicit false is returned as is
+    pub fn token_call(
+        &mut self,
+        caller: H160,
+        token: H160,
+        function: &str,
+        data: ethers::types::Bytes,
+        commit: bool,
+    ) -> Result<bool> {
         let tx = Tx {
-            caller: self.owner,
+            caller,
             transact_to: token,
-            data: calldata.0,
+            data: data.0,
             value: U256::zero(),
-            gas_limit: 5000000,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
+        };
+
+        let output = match self.call_raw(tx, commit)? {
+            ExecutionResult::Success { output, .. } => output.into_data(),
+            ExecutionResult::Revert { output, .. } => {
+                let reason = decode_revert_reason(&output, InstructionResult::Revert);
+                return Err(TokenCallError::Reverted(reason).into());
+            }
+            ExecutionResult::Halt { reason, .. } => {
+                return Err(TokenCallError::Reverted(format!("{reason:?}")).into())
+            }
         };
 
-        let value = if commit { self.call(tx)? } else { self.staticcall(tx)? };
-        let success = self.token.transfer_output(value.output)?;
-        Ok(success)
+        if output.is_empty() {
+            let info = self
+                .evm
+                .db
+                .as_mut()
+                .unwrap()
+                .basic(token.to_alloy())
+                .map_err(|e| anyhow!("Failed to load account {token:?}: {e:?}"))?
+                .unwrap_or_default();
+            if info.code_hash == KECCAK_EMPTY {
+                return Err(TokenCallError::NoCode(token).into());
+            }
+        }
+
+        self.token.success_output(function, output.into())
     }
 
     pub fn simple_transfer(
@@ -473,7 +1971,9 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             transact_to: self.simulator_address,
             data: calldata.0,
             value: U256::zero(),
-            gas_limit: 5000000,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
         };
 
         let value = if commit {
@@ -503,7 +2003,9 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
             transact_to: token_address,
             data: calldata.0,
             value: U256::zero(),
-            gas_limit: 5000000,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            gas_price: U256::zero(),
+            gas_priority_fee: None,
         };
 
         let value = self.staticcall(tx)?;
@@ -543,21 +2045,20 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
 
         // Check if the addresses have some ETH balance
         // If it has, it is likely to be an admin address
+        let block = Some(self.block_number.into());
         let mut tasks = Vec::new();
         for address in address_slots {
             let provider = self.provider.clone();
             tasks.push(async move {
-                let balance =
-                    provider.get_balance(address, None).await.expect("Failed to get balance");
-                if balance > U256::zero() {
-                    Some(address)
-                } else {
-                    None
-                }
+                let balance = provider
+                    .get_balance(address, block)
+                    .await
+                    .map_err(|e| anyhow!("Failed to get the balance of {address:?}: {e:?}"))?;
+                Ok::<_, anyhow::Error>((balance > U256::zero()).then_some(address))
             });
         }
 
-        let results = futures::future::join_all(tasks).await;
+        let results = futures::future::try_join_all(tasks).await?;
         results.into_iter().flatten().for_each(|address| {
             possible_admins.push(address);
         });
@@ -565,3 +2066,93 @@ impl<M: Middleware + 'static> EvmSimulator<M> {
         Ok(possible_admins)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{Fixture, MockToken};
+
+    #[test]
+    fn mainnet_spec_follows_the_fork_schedule() {
+        assert_eq!(spec_id_at(1, 0, 0), SpecId::FRONTIER);
+        assert_eq!(spec_id_at(1, 12_964_999, 0), SpecId::BERLIN);
+        assert_eq!(spec_id_at(1, 12_965_000, 0), SpecId::LONDON);
+        assert_eq!(spec_id_at(1, 15_537_394, 1_663_224_162), SpecId::MERGE);
+        assert_eq!(spec_id_at(1, 17_034_869, 1_681_338_454), SpecId::MERGE);
+        assert_eq!(spec_id_at(1, 17_034_870, 1_681_338_455), SpecId::SHANGHAI);
+        assert_eq!(spec_id_at(1, 19_426_587, 1_710_338_135), SpecId::CANCUN);
+    }
+
+    #[test]
+    fn spec_of_other_chains() {
+        assert_eq!(spec_id_at(8453, 0, 1_686_789_347), SpecId::MERGE);
+        assert_eq!(spec_id_at(42161, 1, 1), SpecId::LATEST);
+    }
+
+    fn fixture_with_balance(kind: MockToken, balance: U256) -> Fixture {
+        let mut fixture = Fixture::new(kind).unwrap();
+        let (token, owner) = (fixture.token, fixture.simulator.owner);
+        fixture.mint(token, owner, balance).unwrap();
+        fixture
+    }
+
+    fn transfer(fixture: &mut Fixture, amount: U256) -> Result<bool> {
+        let (token, owner) = (fixture.token, fixture.simulator.owner);
+        let calldata = fixture.simulator.token.transfer_input(*TRANSFER_RECIPIENT, amount)?;
+        fixture.simulator.token_call(owner, token, "transfer", calldata, true)
+    }
+
+    #[test]
+    fn token_call_returns_true() {
+        let mut fixture = fixture_with_balance(MockToken::Plain, *WEI);
+        assert!(transfer(&mut fixture, *WEI).unwrap());
+    }
+
+    #[test]
+    fn token_call_accepts_missing_return() {
+        let mut fixture = fixture_with_balance(MockToken::NoReturn, *WEI);
+        assert!(transfer(&mut fixture, *WEI).unwrap());
+
+        let (token, recipient) = (fixture.token, *TRANSFER_RECIPIENT);
+        assert_eq!(fixture.simulator.token_balance_of(token, recipient).unwrap(), *WEI);
+    }
+
+    #[test]
+    fn token_call_returns_false() {
+        let mut fixture = fixture_with_balance(MockToken::FalseReturn, *WEI);
+        assert!(!transfer(&mut fixture, *WEI * 2).unwrap());
+    }
+
+    #[test]
+    fn token_call_reverts() {
+        let mut fixture = fixture_with_balance(MockToken::Plain, *WEI);
+        let err = transfer(&mut fixture, *WEI * 2).unwrap_err();
+        match err.downcast_ref::<TokenCallError>() {
+            Some(TokenCallError::Reverted(reason)) => {
+                assert!(reason.contains("insufficient balance"), "{reason}")
+            }
+            other => panic!("expected a revert, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn token_call_without_code() {
+        let mut fixture = Fixture::new(MockToken::Plain).unwrap();
+        let owner = fixture.simulator.owner;
+        let token = H160::repeat_byte(0x42);
+        let calldata = fixture.simulator.token.transfer_input(*TRANSFER_RECIPIENT, *WEI).unwrap();
+        let err =
+            fixture.simulator.token_call(owner, token, "transfer", calldata, true).unwrap_err();
+        assert!(matches!(err.downcast_ref::<TokenCallError>(), Some(TokenCallError::NoCode(_))));
+    }
+
+    #[tokio::test]
+    async fn compliant_tokens_pass_the_suite() {
+        for kind in [MockToken::Plain, MockToken::NoReturn] {
+            let mut fixture = Fixture::new(kind).unwrap();
+            let token = fixture.token;
+            let report = fixture.simulator.check_erc20_compliance(token, *WEI).await.unwrap();
+            assert!(report.passed(), "{kind:?} failed {:?}", report.failed());
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use ethers::types::U256;
+use serde::Serialize;
+
+// Something a token did that is not a tax but still differs from a plain ERC-20
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anomaly {
+    // the buy delivered more than the pool math says (reflection, positive rebase)
+    BuyBonus,
+    // the sell delivered more than the pool math says
+    SellBonus,
+    // the pool math predicted no output at all, so nothing could be measured
+    ZeroOutput,
+}
+
+pub fn bps(part: U256, whole: U256) -> u64 {
+    if whole.is_zero() {
+        return 0;
+    }
+    (part.saturating_mul(U256::from(10000)) / whole).min(U256::from(u64::MAX)).as_u64()
+}
+
+// Difference between what a swap or transfer should have delivered and what actually arrived
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TaxDelta {
+    pub expected: U256,
+    pub actual: U256,
+    // (expected - actual) / expected in basis points, negative when more arrived than expected
+    pub bps: i64,
+}
+
+impl TaxDelta {
+    pub fn new(expected: U256, actual: U256) -> Self {
+        let bps = if actual <= expected {
+            bps(expected - actual, expected).min(i64::MAX as u64) as i64
+        } else {
+            -(bps(actual - expected, expected).min(i64::MAX as u64) as i64)
+        };
+        Self { expected, actual, bps }
+    }
+
+    // A zero expectation has no meaningful ratio, the delta is reported as 0 bps
+    pub fn is_measurable(&self) -> bool {
+        !self.expected.is_zero()
+    }
+
+    pub fn is_bonus(&self) -> bool {
+        self.actual > self.expected
+    }
+
+    // What to report next to the tax, if anything
+    pub fn anomaly(&self, is_buy: bool) -> Option<Anomaly> {
+        if !self.is_measurable() {
+            Some(Anomaly::ZeroOutput)
+        } else if self.is_bonus() {
+            Some(if is_buy { Anomaly::BuyBonus } else { Anomaly::SellBonus })
+        } else {
+            None
+        }
+    }
+
+    // Fraction lost on the way, 0.0 for bonuses
+    pub fn tax_rate(&self) -> f64 {
+        self.bps.max(0) as f64 / 10000.0
+    }
+
+    // Fraction gained on the way, 0.0 for taxed or exact deliveries
+    pub fn bonus_rate(&self) -> f64 {
+        (-self.bps).max(0) as f64 / 10000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bps_of_zero_whole_is_zero() {
+        assert_eq!(bps(U256::from(5), U256::zero()), 0);
+        assert_eq!(bps(U256::zero(), U256::from(1000)), 0);
+    }
+
+    #[test]
+    fn bps_rounds_down_at_the_boundary() {
+        // 1 / 10000 is exactly 1 bps, anything below it rounds to 0
+        assert_eq!(bps(U256::from(1), U256::from(10000)), 1);
+        assert_eq!(bps(U256::from(1), U256::from(10001)), 0);
+        assert_eq!(bps(U256::from(9999), U256::from(10000)), 9999);
+        assert_eq!(bps(U256::from(2), U256::from(3)), 6666);
+    }
+
+    #[test]
+    fn bps_saturates_on_huge_values() {
+        assert_eq!(bps(U256::MAX, U256::from(1)), u64::MAX);
+    }
+
+    #[test]
+    fn taxed_delta() {
+        let delta = TaxDelta::new(U256::from(1000), U256::from(900));
+        assert_eq!(delta.bps, 1000);
+        assert_eq!(delta.tax_rate(), 0.1);
+        assert_eq!(delta.bonus_rate(), 0.0);
+        assert_eq!(delta.anomaly(true), None);
+    }
+
+    #[test]
+    fn negative_delta_is_a_bonus() {
+        let delta = TaxDelta::new(U256::from(1000), U256::from(1050));
+        assert_eq!(delta.bps, -500);
+        assert!(delta.is_bonus());
+        assert_eq!(delta.tax_rate(), 0.0);
+        assert_eq!(delta.bonus_rate(), 0.05);
+        assert_eq!(delta.anomaly(true), Some(Anomaly::BuyBonus));
+        assert_eq!(delta.anomaly(false), Some(Anomaly::SellBonus));
+    }
+
+    #[test]
+    fn zero_expectation_is_not_measurable() {
+        let delta = TaxDelta::new(U256::zero(), U256::from(10));
+        assert!(!delta.is_measurable());
+        assert_eq!(delta.bps, 0);
+        assert_eq!(delta.anomaly(true), Some(Anomaly::ZeroOutput));
+        assert_eq!(delta.anomaly(false), Some(Anomaly::ZeroOutput));
+    }
+
+    #[test]
+    fn exact_delivery() {
+        let delta = TaxDelta::new(U256::from(1000), U256::from(1000));
+        assert_eq!(delta.bps, 0);
+        assert!(!delta.is_bonus());
+        assert_eq!(delta.anomaly(false), None);
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use csv::StringRecord;
 use ethers::{abi::parse_abi, prelude::*};
 use ethers_contract::{Contract, Multicall};
@@ -86,9 +86,43 @@ pub async fn get_implementation<M: Middleware + 'static>(
     Ok(None)
 }
 
+// Binary searches the first block the token has code at, which needs an archive node
+pub async fn get_deployment_block<M: Middleware + 'static>(
+    provider: Arc<M>,
+    token: H160,
+    block_number: U64,
+) -> Result<U64> {
+    let has_code = |number: u64| {
+        let provider = provider.clone();
+        async move {
+            provider
+                .get_code(token, Some(BlockId::Number(BlockNumber::Number(number.into()))))
+                .await
+                .map(|code| !code.is_empty())
+                .map_err(|e| anyhow!("eth_getCode of {token:?} at block {number} failed: {e:?}"))
+        }
+    };
+
+    if !has_code(block_number.as_u64()).await? {
+        return Err(anyhow!("{token:?} has no code at block {block_number}"));
+    }
+    let (mut low, mut high) = (0, block_number.as_u64());
+    while low < high {
+        let mid = (low + high) / 2;
+        if has_code(mid).await? {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    Ok(U64::from(low))
+}
+
 pub async fn get_token_info<M: Middleware + 'static>(
     provider: Arc<M>,
     token: H160,
+    multicall: Option<H160>,
 ) -> Result<Token> {
     let erc20_contract = BaseContract::from(
         parse_abi(&[
@@ -99,7 +133,7 @@ pub async fn get_token_info<M: Middleware + 'static>(
         .unwrap(),
     );
 
-    let mut multicall = Multicall::new(provider.clone(), None).await?;
+    let mut multicall = Multicall::new(provider.clone(), multicall).await?;
     let contract = Contract::new(token, erc20_contract.abi().clone(), provider.clone());
 
     let name_call = contract.method::<_, String>("name", ())?;
@@ -9,7 +9,7 @@ use foundry_common::types::ToEthers;
 use foundry_evm::revm::primitives::keccak256;
 use std::sync::Arc;
 
-use crate::constants::{DEFAULT_CHAIN_ID, DEFAULT_RECIPIENT, DEFAULT_SENDER};
+use crate::constants::{DEFAULT_RECIPIENT, DEFAULT_SENDER};
 
 pub struct EvmTracer<M> {
     provider: Arc<M>,
@@ -122,7 +122,7 @@ impl<M: Middleware + 'static> EvmTracer<M> {
         let sender = sender.unwrap_or(*DEFAULT_SENDER);
         let recipient = *DEFAULT_RECIPIENT;
         let nonce = U256::default();
-        let chain_id = DEFAULT_CHAIN_ID;
+        let chain_id = U64::from(self.provider.get_chainid().await?.as_u64());
         let block_number = self.provider.get_block_number().await.unwrap().as_u64();
         if sender.eq(&owner) {
             return Err(anyhow!("sender must be different from owner"));
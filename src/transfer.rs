@@ -0,0 +1,216 @@
+use ethers::types::{H160, U256};
+use serde::Serialize;
+
+use crate::tax::bps;
+
+// Where the part of a transfer that never reached the recipient went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxMechanism {
+    // sent to the zero/dead address or removed from totalSupply
+    Burn,
+    // kept by the token contract, usually swapped for ETH later
+    Contract,
+    // sent to other wallets (marketing, dev, liquidity)
+    Redirect,
+    // vanished without a Transfer, redistributed to holders through the reflection rate
+    Reflection,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceDelta {
+    pub account: H160,
+    pub before: U256,
+    pub after: U256,
+}
+
+impl BalanceDelta {
+    pub fn gained(&self) -> U256 {
+        self.after.saturating_sub(self.before)
+    }
+
+    pub fn lost(&self) -> U256 {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+// Balance changes of every account a transfer can move tokens to
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedBalances {
+    pub sender: BalanceDelta,
+    pub recipient: BalanceDelta,
+    // the token contract itself
+    pub contract: BalanceDelta,
+    pub zero: BalanceDelta,
+    pub dead: BalanceDelta,
+    // every other account credited in the Transfer logs
+    pub others: Vec<BalanceDelta>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferAnalysis {
+    pub token: H160,
+    pub sender: H160,
+    pub recipient: H160,
+    pub amount: U256,
+    // debited from the sender, can be more than amount
+    pub sent: U256,
+    pub received: U256,
+    pub tax_bps: u64,
+    pub burned: U256,
+    pub to_contract: U256,
+    pub redirected: Vec<(H160, U256)>,
+    pub reflected: U256,
+    pub mechanisms: Vec<TaxMechanism>,
+    pub balances: TrackedBalances,
+    pub total_supply: (U256, U256),
+    pub gas_used: u64,
+}
+
+impl TransferAnalysis {
+    pub fn new(
+        token: H160,
+        amount: U256,
+        balances: TrackedBalances,
+        total_supply: (U256, U256),
+        gas_used: u64,
+    ) -> Self {
+        let sent = balances.sender.lost();
+        let received = balances.recipient.gained();
+        let tax = sent.max(amount).saturating_sub(received);
+
+        let burned = balances
+            .zero
+            .gained()
+            .saturating_add(balances.dead.gained())
+            .saturating_add(total_supply.0.saturating_sub(total_supply.1));
+        let to_contract = balances.contract.gained();
+        let redirected: Vec<(H160, U256)> = balances
+            .others
+            .iter()
+            .filter(|delta| !delta.gained().is_zero())
+            .map(|delta| (delta.account, delta.gained()))
+            .collect();
+        let accounted =
+            redirected.iter().fold(burned.saturating_add(to_contract), |total, (_, amount)| {
+                total.saturating_add(*amount)
+            });
+        let reflected = tax.saturating_sub(accounted);
+
+        let mut mechanisms = Vec::new();
+        if !burned.is_zero() {
+            mechanisms.push(TaxMechanism::Burn);
+        }
+        if !to_contract.is_zero() {
+            mechanisms.push(TaxMechanism::Contract);
+        }
+        if !redirected.is_empty() {
+            mechanisms.push(TaxMechanism::Redirect);
+        }
+        if !reflected.is_zero() {
+            mechanisms.push(TaxMechanism::Reflection);
+        }
+
+        Self {
+            token,
+            sender: balances.sender.account,
+            recipient: balances.recipient.account,
+            amount,
+            sent,
+            received,
+            tax_bps: bps(tax, amount),
+            burned,
+            to_contract,
+            redirected,
+            reflected,
+            mechanisms,
+            balances,
+            total_supply,
+            gas_used,
+        }
+    }
+
+    pub fn is_taxed(&self) -> bool {
+        self.tax_bps > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMOUNT: u64 = 10_000;
+
+    fn delta(account: u64, before: u64, after: u64) -> BalanceDelta {
+        BalanceDelta {
+            account: H160::from_low_u64_be(account),
+            before: U256::from(before),
+            after: U256::from(after),
+        }
+    }
+
+    // A transfer of AMOUNT that delivered `received` to the recipient
+    fn balances(received: u64) -> TrackedBalances {
+        TrackedBalances {
+            sender: delta(1, AMOUNT, 0),
+            recipient: delta(2, 0, received),
+            contract: delta(3, 0, 0),
+            zero: delta(4, 0, 0),
+            dead: delta(5, 0, 0),
+            others: vec![],
+        }
+    }
+
+    fn analyze(balances: TrackedBalances, total_supply: (u64, u64)) -> TransferAnalysis {
+        let total_supply = (U256::from(total_supply.0), U256::from(total_supply.1));
+        TransferAnalysis::new(H160::zero(), U256::from(AMOUNT), balances, total_supply, 0)
+    }
+
+    #[test]
+    fn untaxed_transfer() {
+        let analysis = analyze(balances(AMOUNT), (1_000_000, 1_000_000));
+        assert!(!analysis.is_taxed());
+        assert!(analysis.mechanisms.is_empty());
+    }
+
+    #[test]
+    fn burn_to_dead_address_and_supply() {
+        let mut tracked = balances(9_700);
+        tracked.dead = delta(5, 0, 100);
+        let analysis = analyze(tracked, (1_000_000, 999_800));
+
+        assert_eq!(analysis.tax_bps, 300);
+        assert_eq!(analysis.burned, U256::from(300));
+        assert_eq!(analysis.mechanisms, vec![TaxMechanism::Burn]);
+    }
+
+    #[test]
+    fn redirect_to_fee_wallets() {
+        let mut tracked = balances(9_500);
+        tracked.others = vec![delta(6, 0, 300), delta(7, 50, 250), delta(8, 10, 10)];
+        let analysis = analyze(tracked, (1_000_000, 1_000_000));
+
+        assert_eq!(analysis.tax_bps, 500);
+        assert_eq!(
+            analysis.redirected,
+            vec![
+                (H160::from_low_u64_be(6), U256::from(300)),
+                (H160::from_low_u64_be(7), U256::from(200))
+            ]
+        );
+        assert_eq!(analysis.mechanisms, vec![TaxMechanism::Redirect]);
+        assert!(analysis.reflected.is_zero());
+    }
+
+    #[test]
+    fn kept_by_contract_with_reflection() {
+        let mut tracked = balances(9_000);
+        tracked.contract = delta(3, 1_000, 1_600);
+        let analysis = analyze(tracked, (1_000_000, 1_000_000));
+
+        assert_eq!(analysis.tax_bps, 1000);
+        assert_eq!(analysis.to_contract, U256::from(600));
+        assert_eq!(analysis.reflected, U256::from(400));
+        assert_eq!(analysis.mechanisms, vec![TaxMechanism::Contract, TaxMechanism::Reflection]);
+    }
+}
@@ -21,7 +21,7 @@ pub fn setup_logger() -> Result<()> {
                 message
             ))
         })
-        .chain(std::io::stdout())
+        .chain(std::io::stderr())
         .level(log::LevelFilter::Error)
         .level_for("evm_simulation", LevelFilter::Info)
         .apply()?;